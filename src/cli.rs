@@ -0,0 +1,276 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+use covid_vaccine_efficacy::{
+    demographics::{Sex, StandardPopulation},
+    export::{Format, MetricName},
+    plots::DEFAULT_HEATMAPS,
+    regions::Voivodeship,
+    snapshots::{self, Snapshot, SnapshotFiles},
+    vaccination::Product,
+    AgeGroup, Stratum, YearWeek, AGE_GROUPS,
+};
+
+#[derive(Debug, Parser)]
+#[command(about = "Skuteczność szczepień przeciwko COVID-19 w Polsce")]
+pub(crate) struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum Command {
+    /// Prints tables and draws every plot.
+    Report(Options),
+    /// Prints per age group tables and writes them as CSV files.
+    Tables(Options),
+    /// Draws every plot.
+    Plots(Options),
+    /// Writes per age group tables as CSV files without printing them, along with
+    /// long-format data at full precision.
+    Export(Options),
+    /// Loads every dataset and checks whether they are consistent with each other.
+    Validate(Options),
+}
+
+impl Command {
+    pub fn options(&self) -> &Options {
+        match self {
+            Command::Report(options)
+            | Command::Tables(options)
+            | Command::Plots(options)
+            | Command::Export(options)
+            | Command::Validate(options) => options,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct Options {
+    /// Directory with source data.
+    #[arg(long, default_value = "data")]
+    pub data_dir: PathBuf,
+
+    /// BASiW snapshot date to analyze, e.g. 20211214. Can be repeated to analyze several
+    /// snapshots side by side, each written to its own subdirectory of the output.
+    /// Defaults to the newest snapshot found in the data directory.
+    #[arg(long = "snapshot")]
+    pub snapshots: Vec<Snapshot>,
+
+    /// BASiW file with deaths, overrides the snapshot.
+    #[arg(long)]
+    pub deaths: Option<PathBuf>,
+
+    /// BASiW file with infections, overrides the snapshot.
+    #[arg(long)]
+    pub cases: Option<PathBuf>,
+
+    /// ECDC file with vaccinations. Defaults to `vaccines-pl.csv` in the data directory.
+    #[arg(long)]
+    pub vaccinations: Option<PathBuf>,
+
+    /// GUS table with population by age. Defaults to `tabela01.xls` in the data directory.
+    #[arg(long)]
+    pub population: Option<PathBuf>,
+
+    /// First week of the report, e.g. 2021W27.
+    #[arg(long)]
+    pub from: Option<YearWeek>,
+
+    /// Last week of the report. Defaults to the last week present in the data.
+    #[arg(long)]
+    pub to: Option<YearWeek>,
+
+    /// Age group to report, e.g. 50-59. Can be repeated, defaults to all of them.
+    #[arg(long = "age-group")]
+    pub age_groups: Vec<AgeGroup>,
+
+    /// Besides both sexes together, report women and men separately.
+    #[arg(long)]
+    pub by_sex: bool,
+
+    /// Besides the whole country, report every voivodeship separately.
+    #[arg(long)]
+    pub by_region: bool,
+
+    /// Report only given voivodeship, e.g. 30, PL41 or wielkopolskie. Can be repeated.
+    #[arg(long = "region", conflicts_with = "by_region")]
+    pub regions: Vec<Voivodeship>,
+
+    /// Besides all deaths, report deaths with and without comorbidities separately.
+    /// There is no population data for them, so only deaths are reported.
+    #[arg(long)]
+    pub by_comorbidities: bool,
+
+    /// Report only deaths with (true) or without (false) comorbidities.
+    #[arg(long, conflicts_with = "by_comorbidities")]
+    pub comorbidities: Option<bool>,
+
+    /// Besides all deaths, report deaths with and without reduced immunity separately.
+    /// There is no population data for them, so only deaths are reported.
+    #[arg(long)]
+    pub by_reduced_immunity: bool,
+
+    /// Report only deaths with (true) or without (false) reduced immunity.
+    #[arg(long, conflicts_with = "by_reduced_immunity")]
+    pub reduced_immunity: Option<bool>,
+
+    /// Besides all vaccines, report every one of them separately. Vaccinated people are
+    /// compared against all unvaccinated ones.
+    #[arg(long)]
+    pub by_product: bool,
+
+    /// Report only people vaccinated with given vaccine, e.g. COM or pfizer. Can be repeated.
+    #[arg(long = "product", conflicts_with = "by_product")]
+    pub products: Vec<Product>,
+
+    /// Weeks after a dose before people are counted as having it, e.g. 2 for the usual
+    /// 14 days to full protection. Until then they keep their previous status.
+    #[arg(long, default_value_t = 0)]
+    pub protection_lag: usize,
+
+    /// Population age groups are weighted with in age-standardized rates: gus (Polish
+    /// population of 2021) or esp (European Standard Population 2013).
+    #[arg(long, default_value = "gus")]
+    pub standard_population: StandardPopulation,
+
+    /// Format of long-format data written by `export`: csv, json or parquet. Can be
+    /// repeated, defaults to all of them.
+    #[arg(long = "format")]
+    pub formats: Vec<Format>,
+
+    /// Metric drawn by `plots` and `report` as a heatmap of age groups by week, named as in
+    /// long-format data, e.g. deaths_per_million:unvaccinated, death_rate_ratio:two_doses
+    /// or coverage:three_doses. Can be repeated, defaults to those three (with two doses
+    /// for coverage).
+    #[arg(long = "heatmap")]
+    pub heatmaps: Vec<MetricName>,
+
+    /// Directory where tables and plots are written.
+    #[arg(long, default_value = "output")]
+    pub output: PathBuf,
+}
+
+impl Options {
+    /// Strata to report, every combination of requested values of each dimension. The whole
+    /// population comes first.
+    pub fn strata(&self) -> Vec<Stratum> {
+        let sexes = dimension(self.by_sex, &[], Sex::KNOWN.iter().copied());
+        let regions = dimension(self.by_region, &self.regions, Voivodeship::all());
+        let comorbidities = dimension(
+            self.by_comorbidities,
+            self.comorbidities.as_slice(),
+            [true, false].into_iter(),
+        );
+        let reduced_immunity = dimension(
+            self.by_reduced_immunity,
+            self.reduced_immunity.as_slice(),
+            [true, false].into_iter(),
+        );
+        let products = dimension(
+            self.by_product,
+            &self.products,
+            Product::KNOWN.iter().copied(),
+        );
+
+        let mut strata = Vec::new();
+        for region in &regions {
+            for sex in &sexes {
+                for comorbidities in &comorbidities {
+                    for reduced_immunity in &reduced_immunity {
+                        for product in &products {
+                            strata.push(Stratum {
+                                sex: *sex,
+                                region: *region,
+                                comorbidities: *comorbidities,
+                                reduced_immunity: *reduced_immunity,
+                                product: *product,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        strata
+    }
+
+    /// Strata of each sex a stratum of both of them is adjusted for sex with, when reporting
+    /// by sex.
+    pub fn sexes_of(&self, stratum: &Stratum) -> Option<Vec<Stratum>> {
+        (self.by_sex && stratum.sex.is_none() && stratum.has_denominator()).then(|| {
+            Sex::KNOWN
+                .iter()
+                .map(|sex| Stratum {
+                    sex: Some(*sex),
+                    ..*stratum
+                })
+                .collect()
+        })
+    }
+
+    pub fn vaccinations(&self) -> PathBuf {
+        self.vaccinations
+            .clone()
+            .unwrap_or_else(|| self.data_dir.join("vaccines-pl.csv"))
+    }
+
+    pub fn population(&self) -> PathBuf {
+        self.population
+            .clone()
+            .unwrap_or_else(|| self.data_dir.join("tabela01.xls"))
+    }
+
+    /// Directory where results of a given snapshot are written. When several snapshots
+    /// are analyzed, each one gets its own subdirectory.
+    pub fn output_of(&self, snapshot: Option<Snapshot>) -> PathBuf {
+        match snapshot {
+            Some(snapshot) if self.snapshots.len() > 1 => self.output.join(snapshot.to_string()),
+            _ => self.output.clone(),
+        }
+    }
+
+    pub fn snapshot_files(&self) -> anyhow::Result<Vec<SnapshotFiles>> {
+        snapshots::resolve(
+            &self.data_dir,
+            self.deaths.as_deref(),
+            self.cases.as_deref(),
+            &self.snapshots,
+        )
+    }
+
+    pub fn formats(&self) -> &[Format] {
+        if self.formats.is_empty() {
+            Format::ALL
+        } else {
+            &self.formats
+        }
+    }
+
+    pub fn heatmaps(&self) -> &[MetricName] {
+        if self.heatmaps.is_empty() {
+            DEFAULT_HEATMAPS
+        } else {
+            &self.heatmaps
+        }
+    }
+
+    pub fn age_groups(&self) -> Vec<AgeGroup> {
+        if self.age_groups.is_empty() {
+            AGE_GROUPS.to_vec()
+        } else {
+            self.age_groups.clone()
+        }
+    }
+}
+
+/// Values of a single stratification dimension, `None` standing for all of them.
+fn dimension<T: Copy>(by: bool, only: &[T], all: impl Iterator<Item = T>) -> Vec<Option<T>> {
+    if by {
+        std::iter::once(None).chain(all.map(Some)).collect()
+    } else if !only.is_empty() {
+        only.iter().copied().map(Some).collect()
+    } else {
+        vec![None]
+    }
+}
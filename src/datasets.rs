@@ -0,0 +1,302 @@
+//! Source datasets loaded together and turned into reports.
+
+use std::path::Path;
+
+use crate::{
+    deaths::{DeathsData, InfectionsData, VaccinationStatus},
+    demographics::{age_distribution, AgeDistribution, StandardPopulation},
+    quality::DataQuality,
+    snapshots::SnapshotFiles,
+    vaccination::VaccinationData,
+    weeks, AgeGroup, DeathRate, Stratum, WeeklyReport, WeeklyReports, YearWeek,
+};
+
+/// Every source dataset: vaccinations (ECDC), population (GUS), deaths and infections
+/// (BASiW).
+pub struct Datasets {
+    /// Population by age, with regional shares taken from the vaccination data.
+    pub ages: AgeDistribution,
+    /// Vaccinated people by week, age group, region and vaccine.
+    pub vaccinations: VaccinationData,
+    /// Deaths by vaccination status.
+    pub deaths: DeathsData,
+    /// Infections by vaccination status.
+    pub cases: InfectionsData,
+    /// Weeks after a dose before people are counted as having it, zero by default.
+    pub protection_lag: usize,
+}
+
+impl Datasets {
+    /// Loads the ECDC and GUS files along with a BASiW snapshot.
+    pub fn load(
+        vaccinations: &Path,
+        population: &Path,
+        files: &SnapshotFiles,
+    ) -> anyhow::Result<Self> {
+        let vaccinations = VaccinationData::new(vaccinations)?;
+        let ages =
+            age_distribution(population)?.with_regional_shares(vaccinations.regional_shares());
+        let deaths = DeathsData::new(&files.deaths)?;
+        let cases = InfectionsData::new(&files.cases)?;
+
+        Ok(Self {
+            ages,
+            vaccinations,
+            deaths,
+            cases,
+            protection_lag: 0,
+        })
+    }
+
+    /// Rows dropped from every source file, along with cells clamped in reports, e.g. by
+    /// `clamped_cells`.
+    pub fn quality(&self, clamped_cells: Vec<String>) -> DataQuality<'_> {
+        DataQuality::new(
+            &self.deaths.rejections,
+            &self.cases.rejections,
+            &self.vaccinations.rejections,
+            clamped_cells,
+        )
+    }
+
+    /// Last week both deaths and infections are known of.
+    pub fn max_week(&self) -> YearWeek {
+        self.cases.max_week().min(self.deaths.max_week())
+    }
+
+    /// Report of a single week and age group.
+    pub fn weekly_report(
+        &self,
+        week: YearWeek,
+        age_group: AgeGroup,
+        stratum: &Stratum,
+    ) -> WeeklyReport {
+        self.lagged_report(week, age_group, stratum, self.protection_lag)
+    }
+
+    /// Share of people of both sexes in an age group the stratum's sex makes up. ECDC
+    /// doesn't report vaccinations by sex, so the coverage is assumed to be the same for
+    /// both of them.
+    fn sex_share(&self, age_group: AgeGroup, stratum: &Stratum) -> f64 {
+        let both = Stratum {
+            sex: None,
+            ..*stratum
+        };
+        self.ages.population_of(age_group, stratum) as f64
+            / self.ages.population_of(age_group, &both) as f64
+    }
+
+    /// People who got at least one dose of any vaccine at least `lag` weeks before the
+    /// week: as of its end, and in person-weeks within it. Unvaccinated people didn't get
+    /// any particular vaccine, so within a product's stratum they still are everyone who
+    /// didn't get any of them.
+    fn vaccinated_with_any_product(
+        &self,
+        week: YearWeek,
+        age_group: AgeGroup,
+        stratum: &Stratum,
+        lag: usize,
+    ) -> (usize, f64) {
+        let protected = week.weeks_before(lag).unwrap_or(week);
+        let any_product = Stratum {
+            product: None,
+            ..*stratum
+        };
+        let sex_share = self.sex_share(age_group, stratum);
+        let people = self
+            .vaccinations
+            .sum(age_group, protected, &any_product)
+            .scale(sex_share);
+        let person_weeks = self
+            .vaccinations
+            .person_weeks(age_group, protected, &any_product)
+            .scale(sex_share);
+        (people.at_least_one_dose, person_weeks.at_least_one_dose)
+    }
+
+    /// Report whose vaccinated people are those who got their doses at least `lag` weeks
+    /// before the week. Unvaccinated people are clamped at zero where more people got
+    /// vaccinated than live in the stratum, which `clamped_cells` reports.
+    fn lagged_report(
+        &self,
+        week: YearWeek,
+        age_group: AgeGroup,
+        stratum: &Stratum,
+        lag: usize,
+    ) -> WeeklyReport {
+        let population = self.ages.population_of(age_group, stratum);
+        let protected = week.weeks_before(lag).unwrap_or(week);
+        let sex_share = self.sex_share(age_group, stratum);
+        let vaccinated_people = self
+            .vaccinations
+            .sum(age_group, protected, stratum)
+            .scale(sex_share);
+
+        let any_product = Stratum {
+            product: None,
+            ..*stratum
+        };
+        let stratum_of = |status| match status {
+            VaccinationStatus::Unvaccinated => &any_product,
+            _ => stratum,
+        };
+        let (vaccinated, vaccinated_person_weeks) =
+            self.vaccinated_with_any_product(week, age_group, stratum, lag);
+        let unvaccinated_people = population.saturating_sub(vaccinated);
+
+        let by_status = |data: &dyn Fn(VaccinationStatus) -> usize| DeathRate {
+            unvaccinated: data(VaccinationStatus::Unvaccinated),
+            one_dose: data(VaccinationStatus::OneDose),
+            two_doses: data(VaccinationStatus::TwoDoses),
+            three_doses: data(VaccinationStatus::ThreeDoses),
+        };
+
+        let absolute_deaths = by_status(&|status| {
+            self.deaths
+                .by_vaccination_status(week, age_group, stratum_of(status), status)
+        });
+        let absolute_cases = by_status(&|status| {
+            self.cases
+                .by_vaccination_status(week, age_group, stratum_of(status), status)
+        });
+
+        // Uptake within the week is interpolated, so people vaccinated during it count as
+        // half a person-week of their new status.
+        let person_weeks = self
+            .vaccinations
+            .person_weeks(age_group, protected, stratum)
+            .scale(sex_share);
+        let unvaccinated_person_weeks = (population as f64 - vaccinated_person_weeks).max(0.0);
+
+        WeeklyReport::new(
+            vaccinated_people,
+            unvaccinated_people,
+            absolute_cases,
+            absolute_deaths,
+        )
+        .with_person_weeks(DeathRate {
+            unvaccinated: unvaccinated_person_weeks,
+            one_dose: person_weeks.one_dose,
+            two_doses: person_weeks.two_doses,
+            three_doses: person_weeks.three_doses,
+        })
+    }
+
+    /// Weeks from `from` to `to`, clamped to the available data, which starts with 2021.
+    pub fn weeks(&self, from: Option<YearWeek>, to: Option<YearWeek>) -> Vec<YearWeek> {
+        let min_week = from.unwrap_or(YearWeek((2020, 1)));
+        let max_week = match to {
+            Some(to) => to.min(self.max_week()),
+            None => self.max_week(),
+        };
+        weeks(min_week, max_week).collect()
+    }
+
+    /// Reports of given weeks and age groups of a stratum.
+    pub fn weekly_reports(
+        &self,
+        weeks: &[YearWeek],
+        age_groups: &[AgeGroup],
+        stratum: Stratum,
+        standard_population: StandardPopulation,
+    ) -> anyhow::Result<WeeklyReports> {
+        self.weekly_reports_with_lag(
+            weeks,
+            age_groups,
+            stratum,
+            standard_population,
+            self.protection_lag,
+        )
+    }
+
+    /// Reports of given weeks and age groups of a stratum, with a protection lag other
+    /// than the configured one.
+    pub fn weekly_reports_with_lag(
+        &self,
+        weeks: &[YearWeek],
+        age_groups: &[AgeGroup],
+        stratum: Stratum,
+        standard_population: StandardPopulation,
+        lag: usize,
+    ) -> anyhow::Result<WeeklyReports> {
+        let mut builder = WeeklyReports::builder().stratum(stratum).standard_weights(
+            standard_population,
+            standard_population.weights(&self.ages, age_groups),
+        );
+        for week in weeks {
+            for age_group in age_groups {
+                builder = builder.report(
+                    *week,
+                    *age_group,
+                    self.lagged_report(*week, *age_group, &stratum, lag),
+                );
+            }
+        }
+        builder.build()
+    }
+
+    /// Weeks and age groups of a stratum where more people got vaccinated than live in it,
+    /// so that their unvaccinated people are clamped at zero.
+    pub fn clamped_cells(
+        &self,
+        weeks: &[YearWeek],
+        age_groups: &[AgeGroup],
+        stratum: &Stratum,
+    ) -> Vec<String> {
+        let mut problems = Vec::new();
+        for week in weeks {
+            for age_group in age_groups {
+                let population = self.ages.population_of(*age_group, stratum);
+                let (vaccinated, person_weeks) = self.vaccinated_with_any_product(
+                    *week,
+                    *age_group,
+                    stratum,
+                    self.protection_lag,
+                );
+                if vaccinated > population || person_weeks > population as f64 {
+                    problems.push(format!(
+                        "{}, grupa wiekowa {}, {}: zaszczepionych ({}) więcej niż populacja ({})",
+                        week,
+                        age_group,
+                        stratum,
+                        vaccinated.max(person_weeks.round() as usize),
+                        population
+                    ));
+                }
+            }
+        }
+        problems
+    }
+
+    /// Returns a list of problems found in the data of given strata.
+    pub fn validate(
+        &self,
+        weeks: &[YearWeek],
+        age_groups: &[AgeGroup],
+        strata: &[Stratum],
+    ) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.deaths.max_week() != self.cases.max_week() {
+            problems.push(format!(
+                "dane o zgonach kończą się w {}, a o infekcjach w {}",
+                self.deaths.max_week(),
+                self.cases.max_week()
+            ));
+        }
+
+        let inconsistent_counties = self.deaths.inconsistent_counties();
+        if inconsistent_counties > 0 {
+            problems.push(format!(
+                "{} zgonów ma powiat spoza swojego województwa",
+                inconsistent_counties
+            ));
+        }
+
+        for stratum in strata {
+            problems.extend(self.clamped_cells(weeks, age_groups, stratum));
+        }
+
+        problems
+    }
+}
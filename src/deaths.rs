@@ -1,15 +1,25 @@
+//! Deaths and infections by vaccination status, from BASiW.
+
+use std::{collections::HashMap, path::Path};
+
 use chrono::{Datelike, NaiveDate};
-use serde::{Deserialize, Deserializer};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
 
-use crate::{AgeGroup, YearWeek};
+use crate::{
+    demographics::Sex,
+    errors::{self, InvalidValue, Location},
+    quality::{Rejections, Sample},
+    regions::{deserialize_teryt, Voivodeship},
+    vaccination::Product,
+    AgeGroup, Stratum, YearWeek,
+};
 
 fn deserialize_date<'a, D>(deserializer: D) -> Result<NaiveDate, D::Error>
 where
     D: Deserializer<'a>,
 {
     let text: &str = Deserialize::deserialize(deserializer)?;
-    NaiveDate::parse_from_str(text, "%Y-%m-%d")
-        .map_err(|_| serde::de::Error::custom(format!("bad date: '{}'", text)))
+    NaiveDate::parse_from_str(text, "%Y-%m-%d").map_err(|_| InvalidValue::Date.error())
 }
 
 fn deserialize_age<'a, D>(deserializer: D) -> Result<usize, D::Error>
@@ -17,14 +27,25 @@ where
     D: Deserializer<'a>,
 {
     let text: &str = Deserialize::deserialize(deserializer)?;
-    let age: f32 = text
-        .parse()
-        .map_err(|_| serde::de::Error::custom(format!("bad age: '{}'", text)))?;
+    let age: f32 = text.parse().map_err(|_| InvalidValue::Age.error())?;
     Ok(age.round() as usize)
 }
 
-#[derive(Debug, Deserialize, PartialEq, Clone, Copy)]
-pub(crate) enum VaccinationStatus {
+fn deserialize_flag<'a, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'a>,
+{
+    let text: &str = Deserialize::deserialize(deserializer)?;
+    match text {
+        "1" => Ok(true),
+        "0" => Ok(false),
+        _ => Err(InvalidValue::Flag.error()),
+    }
+}
+
+/// Doses the dead or infected got before, as reported by BASiW.
+#[derive(Debug, Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum VaccinationStatus {
     #[serde(rename = "")]
     Unvaccinated,
     #[serde(rename = "jedna_dawka")]
@@ -41,8 +62,24 @@ pub(crate) struct CovidDeath {
     date: NaiveDate,
     #[serde(alias = "wiek", deserialize_with = "deserialize_age")]
     pub age: usize,
+    #[serde(alias = "teryt_woj", deserialize_with = "deserialize_teryt")]
+    pub voivodeship: Voivodeship,
+    /// TERYT code of the powiat, e.g. 407. There is no vaccination data for powiats,
+    /// so it's not used for stratification.
+    #[serde(alias = "teryt_pow")]
+    pub county: Option<u16>,
+    #[serde(alias = "plec")]
+    pub sex: Sex,
+    #[serde(alias = "producent")]
+    pub product: Option<Product>,
     #[serde(alias = "dawka_ost")]
     pub vaccination_status: VaccinationStatus,
+    #[serde(alias = "czy_wspolistniejace", deserialize_with = "deserialize_flag")]
+    pub comorbidities: bool,
+    #[serde(alias = "obniz_odpornosc", deserialize_with = "deserialize_flag")]
+    pub reduced_immunity: bool,
+    #[serde(alias = "liczba_zaraportowanych_zgonow")]
+    pub count: usize,
 }
 
 #[derive(Debug, Deserialize, Clone, Copy)]
@@ -51,118 +88,396 @@ pub(crate) struct Cases {
     date: NaiveDate,
     #[serde(alias = "wiek", deserialize_with = "deserialize_age")]
     pub age: usize,
+    #[serde(alias = "teryt_woj", deserialize_with = "deserialize_teryt")]
+    pub voivodeship: Voivodeship,
+    #[serde(alias = "plec")]
+    pub sex: Sex,
+    #[serde(alias = "producent")]
+    pub product: Option<Product>,
     #[serde(alias = "dawka_ost")]
     pub vaccination_status: VaccinationStatus,
     #[serde(alias = "liczba_zaraportowanych_zakazonych")]
     pub count: usize,
 }
 
-pub(crate) struct DeathsData {
+impl CovidDeath {
+    fn stratum(&self) -> Stratum {
+        Stratum {
+            sex: Some(self.sex),
+            region: Some(self.voivodeship),
+            comorbidities: Some(self.comorbidities),
+            reduced_immunity: Some(self.reduced_immunity),
+            product: self.product,
+        }
+    }
+}
+
+impl Cases {
+    /// Infections don't come with comorbidities, so they only belong to strata which
+    /// don't care about them.
+    fn stratum(&self) -> Stratum {
+        Stratum {
+            sex: Some(self.sex),
+            region: Some(self.voivodeship),
+            comorbidities: None,
+            reduced_immunity: None,
+            product: self.product,
+        }
+    }
+}
+
+/// Last week of records, which have been checked not to be empty.
+fn max_week(dates: impl Iterator<Item = NaiveDate>) -> YearWeek {
+    dates
+        .map(|date| YearWeek::from(date.iso_week()))
+        .max()
+        .unwrap_or_default()
+}
+
+/// Reported count of records of a single age and stratum.
+struct Count {
+    age: usize,
+    stratum: Stratum,
+    count: usize,
+}
+
+/// Every stratum a record of a given stratum belongs to, from its own down to the whole
+/// population, i.e. its stratum with any of the fields it knows about left out.
+fn strata_of(record: Stratum) -> Vec<Stratum> {
+    let generalizations: [fn(&mut Stratum); 5] = [
+        |stratum| stratum.sex = None,
+        |stratum| stratum.region = None,
+        |stratum| stratum.comorbidities = None,
+        |stratum| stratum.reduced_immunity = None,
+        |stratum| stratum.product = None,
+    ];
+    let mut strata = vec![record];
+    for generalize in generalizations {
+        for n in 0..strata.len() {
+            let mut stratum = strata[n];
+            generalize(&mut stratum);
+            if stratum != strata[n] {
+                strata.push(stratum);
+            }
+        }
+    }
+    strata
+}
+
+/// Reported counts summed up at load time by week, vaccination status, age and every
+/// stratum records belong to, so that a report's cell takes a lookup per year of age
+/// instead of a scan of records.
+#[derive(Default)]
+struct WeeklyCounts {
+    sums: HashMap<(YearWeek, VaccinationStatus, usize, Stratum), usize>,
+    max_age: usize,
+}
+
+impl WeeklyCounts {
+    fn new(records: impl Iterator<Item = (NaiveDate, VaccinationStatus, Count)>) -> Self {
+        let mut counts = WeeklyCounts::default();
+        for (date, status, record) in records {
+            let week = YearWeek::from(date.iso_week());
+            for stratum in strata_of(record.stratum) {
+                *counts
+                    .sums
+                    .entry((week, status, record.age, stratum))
+                    .or_default() += record.count;
+            }
+            counts.max_age = counts.max_age.max(record.age);
+        }
+        counts
+    }
+
+    fn sum(
+        &self,
+        week: YearWeek,
+        age_group: AgeGroup,
+        stratum: &Stratum,
+        vaccination_status: VaccinationStatus,
+    ) -> usize {
+        let to = age_group.to.unwrap_or(self.max_age);
+        (age_group.from..=to)
+            .filter_map(|age| self.sums.get(&(week, vaccination_status, age, *stratum)))
+            .sum()
+    }
+}
+
+/// Error of a BASiW file as a whole. Malformed records are only dropped.
+#[derive(Debug, thiserror::Error)]
+pub enum BasiwError {
+    #[error("can't open {location}")]
+    Open {
+        location: Location,
+        source: std::io::Error,
+    },
+    #[error("{location}: malformed CSV file")]
+    Csv {
+        location: Location,
+        source: csv::Error,
+    },
+    #[error("{location}: no usable records")]
+    Empty { location: Location },
+}
+
+/// Reads a BASiW file. Records which can't be interpreted are dropped and accounted for,
+/// errors of the file itself are returned. Each record reports people in `count_column`,
+/// a dropped one whose count can't be read is assumed to report a single person.
+fn read_records<T: DeserializeOwned>(
+    path: &Path,
+    count_column: &str,
+) -> Result<(Vec<T>, Rejections), BasiwError> {
+    let csv_error = |source: csv::Error| BasiwError::Csv {
+        location: Location::csv(path, &source),
+        source,
+    };
+
+    let file = std::fs::File::open(path).map_err(|source| BasiwError::Open {
+        location: Location::file(path),
+        source,
+    })?;
+    let transcoded = encoding_rs_io::DecodeReaderBytesBuilder::new()
+        .encoding(Some(encoding_rs::ISO_8859_2))
+        .build(file);
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(b';')
+        .flexible(true)
+        .from_reader(transcoded);
+    let headers = reader.headers().map_err(csv_error)?.clone();
+    let count_column = headers.iter().position(|header| header == count_column);
+
+    let mut records = Vec::new();
+    let mut rejections = Rejections::new(path);
+    for record in reader.records() {
+        let record = record.map_err(csv_error)?;
+        let count = count_column
+            .and_then(|column| record.get(column))
+            .and_then(|count| count.parse().ok())
+            .unwrap_or(1);
+        let sample = |message: String| Sample {
+            line: record.position().map(|position| position.line()),
+            message,
+            row: record.iter().map(str::to_owned).collect(),
+        };
+
+        if record.len() != headers.len() {
+            let message = format!("{} fields instead of {}", record.len(), headers.len());
+            rejections.reject("wrong number of fields".to_owned(), count, sample(message));
+            continue;
+        }
+        match record.deserialize(Some(&headers)) {
+            Ok(parsed) => {
+                rejections.accept(count);
+                records.push(parsed);
+            }
+            Err(error) => {
+                let reason = match errors::reason(&error, &headers) {
+                    Some(reason) => reason,
+                    None => return Err(csv_error(error)),
+                };
+                rejections.reject(reason, count, sample(errors::message(&error)));
+            }
+        }
+    }
+
+    if records.is_empty() {
+        return Err(BasiwError::Empty {
+            location: Location::file(path),
+        });
+    }
+    Ok((records, rejections))
+}
+
+/// Deaths reported by BASiW.
+pub struct DeathsData {
+    /// Deaths of every record which has been read.
     pub total_deaths: usize,
-    deaths: Vec<CovidDeath>,
+    /// Records dropped while reading.
+    pub rejections: Rejections,
+    max_week: YearWeek,
+    inconsistent_counties: usize,
+    counts: WeeklyCounts,
 }
 
 impl DeathsData {
-    pub fn new() -> Self {
-        let file = std::fs::File::open("data/ewp_dsh_zgony_po_szczep_202202010941.csv").unwrap();
-        let transcoded = encoding_rs_io::DecodeReaderBytesBuilder::new()
-            .encoding(Some(encoding_rs::ISO_8859_2))
-            .build(file);
-
-        let deaths: Vec<_> = csv::ReaderBuilder::new()
-            .delimiter(b';')
-            .from_reader(transcoded)
-            .deserialize::<CovidDeath>()
-            .filter_map(|r| match r {
-                Ok(r) => Some(r),
-                Err(e) => {
-                    println!("dropping record: {}", e);
-                    None
-                }
-            })
-            .collect();
-
-        Self {
-            total_deaths: deaths.len(),
-            deaths,
-        }
+    /// Reads a BASiW file with deaths.
+    pub fn new(path: &Path) -> Result<Self, BasiwError> {
+        let (deaths, rejections): (Vec<CovidDeath>, _) =
+            read_records(path, "liczba_zaraportowanych_zgonow")?;
+
+        Ok(Self {
+            total_deaths: deaths.iter().map(|death| death.count).sum(),
+            rejections,
+            max_week: max_week(deaths.iter().map(|death| death.date)),
+            inconsistent_counties: deaths
+                .iter()
+                .filter(|death| {
+                    death
+                        .county
+                        .is_some_and(|county| county / 100 != u16::from(death.voivodeship.teryt()))
+                })
+                .count(),
+            counts: WeeklyCounts::new(deaths.iter().map(|death| {
+                let count = Count {
+                    age: death.age,
+                    stratum: death.stratum(),
+                    count: death.count,
+                };
+                (death.date, death.vaccination_status, count)
+            })),
+        })
+    }
+
+    /// Number of records whose powiat doesn't belong to their voivodeship.
+    pub fn inconsistent_counties(&self) -> usize {
+        self.inconsistent_counties
     }
 
+    /// Last week of records.
     pub fn max_week(&self) -> YearWeek {
-        self.deaths
-            .iter()
-            .map(|cases| YearWeek::from(cases.date.iso_week()))
-            .max()
-            .unwrap()
+        self.max_week
     }
 
+    /// Reported people of a week, age group and stratum with a given vaccination status.
     pub fn by_vaccination_status(
         &self,
         week: YearWeek,
         age_group: AgeGroup,
+        stratum: &Stratum,
         vaccination_status: VaccinationStatus,
     ) -> usize {
-        self.deaths
-            .iter()
-            .filter(|death| {
-                YearWeek::from(death.date.iso_week()) == week
-                    && age_group.includes(death.age)
-                    && death.vaccination_status == vaccination_status
-            })
-            .count()
+        self.counts
+            .sum(week, age_group, stratum, vaccination_status)
     }
 }
 
-pub(crate) struct InfectionsData {
-    pub cases: Vec<Cases>,
+/// Infections reported by BASiW.
+pub struct InfectionsData {
+    /// Records dropped while reading.
+    pub rejections: Rejections,
+    max_week: YearWeek,
+    counts: WeeklyCounts,
 }
 
 impl InfectionsData {
-    pub fn new() -> Self {
-        let file =
-            std::fs::File::open("data/ewp_dsh_zakazenia_po_szczepieniu_202202010940.csv").unwrap();
-        let transcoded = encoding_rs_io::DecodeReaderBytesBuilder::new()
-            .encoding(Some(encoding_rs::ISO_8859_2))
-            .build(file);
-
-        let cases: Vec<_> = csv::ReaderBuilder::new()
-            .delimiter(b';')
-            .from_reader(transcoded)
-            .deserialize::<Cases>()
-            .filter_map(|r| match r {
-                Ok(r) => Some(r),
-                Err(e) => {
-                    println!("dropping case record: {}", e);
-                    None
-                }
-            })
-            .collect();
-
-        Self { cases }
+    /// Reads a BASiW file with infections.
+    pub fn new(path: &Path) -> Result<Self, BasiwError> {
+        let (cases, rejections): (Vec<Cases>, _) =
+            read_records(path, "liczba_zaraportowanych_zakazonych")?;
+
+        Ok(Self {
+            rejections,
+            max_week: max_week(cases.iter().map(|case| case.date)),
+            counts: WeeklyCounts::new(cases.iter().map(|case| {
+                let count = Count {
+                    age: case.age,
+                    stratum: case.stratum(),
+                    count: case.count,
+                };
+                (case.date, case.vaccination_status, count)
+            })),
+        })
     }
 
+    /// Last week of records.
     pub fn max_week(&self) -> YearWeek {
-        self.cases
-            .iter()
-            .map(|cases| YearWeek::from(cases.date.iso_week()))
-            .max()
-            .unwrap()
+        self.max_week
     }
 
+    /// Reported people of a week, age group and stratum with a given vaccination status.
     pub fn by_vaccination_status(
         &self,
         week: YearWeek,
         age_group: AgeGroup,
+        stratum: &Stratum,
         vaccination_status: VaccinationStatus,
     ) -> usize {
-        self.cases
-            .iter()
-            .filter(|death| {
-                YearWeek::from(death.date.iso_week()) == week
-                    && age_group.includes(death.age)
-                    && death.vaccination_status == vaccination_status
-            })
-            .map(|cases| cases.count)
-            .sum()
+        self.counts
+            .sum(week, age_group, stratum, vaccination_status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(text: &str) -> VaccinationStatus {
+        let (status,): (VaccinationStatus,) = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(text.as_bytes())
+            .deserialize()
+            .next()
+            .unwrap()
+            .unwrap();
+        status
+    }
+
+    #[test]
+    fn every_spelling_of_a_vaccination_status_is_recognized() {
+        assert_eq!(status("\"\""), VaccinationStatus::Unvaccinated);
+        assert_eq!(status("jedna_dawka"), VaccinationStatus::OneDose);
+        assert_eq!(status("dwie_dawki"), VaccinationStatus::TwoDoses);
+        assert_eq!(status("pelna_dawka"), VaccinationStatus::TwoDoses);
+        assert_eq!(status("przypominajaca"), VaccinationStatus::ThreeDoses);
+    }
+
+    #[test]
+    fn booster_mangled_by_the_wrong_encoding_is_recognized() {
+        // BASiW writes "uzupełniająca" in windows-1250, which read as ISO-8859-2 turns ą into š.
+        let (text, _, _) = encoding_rs::ISO_8859_2.decode(b"uzupe\xb3niaj\xb9ca");
+        assert_eq!(text, "uzupełniajšca");
+        assert_eq!(status(&text), VaccinationStatus::ThreeDoses);
+    }
+
+    #[test]
+    fn records_are_counted_by_iso_weeks() {
+        let counts = WeeklyCounts::new(
+            [(2022, 1, 2, 2), (2022, 1, 3, 3), (2022, 1, 9, 5)]
+                .into_iter()
+                .map(|(year, month, day, count)| {
+                    let count = Count {
+                        age: 80,
+                        stratum: Stratum::default(),
+                        count,
+                    };
+                    let date = NaiveDate::from_ymd_opt(year, month, day).unwrap();
+                    (date, VaccinationStatus::Unvaccinated, count)
+                }),
+        );
+        let sum = |week| {
+            counts.sum(
+                week,
+                AgeGroup::open_ended(80),
+                &Stratum::default(),
+                VaccinationStatus::Unvaccinated,
+            )
+        };
+
+        assert_eq!(sum(YearWeek((2021, 52))), 2);
+        assert_eq!(sum(YearWeek((2022, 1))), 8);
+    }
+
+    #[test]
+    fn records_belong_to_every_stratum_leaving_out_what_they_know() {
+        let death = Stratum {
+            sex: Some(Sex::Female),
+            region: Voivodeship::from_teryt(14),
+            comorbidities: Some(true),
+            reduced_immunity: Some(false),
+            product: Some(Product::Janssen),
+        };
+        let strata = strata_of(death);
+        assert_eq!(strata.len(), 32);
+        assert!(strata.iter().all(|stratum| stratum.includes(&death)));
+        assert!(strata.contains(&Stratum::default()));
+
+        // Infections of the unvaccinated know neither comorbidities nor a product.
+        let case = Stratum {
+            comorbidities: None,
+            reduced_immunity: None,
+            product: None,
+            ..death
+        };
+        assert_eq!(strata_of(case).len(), 4);
     }
 }
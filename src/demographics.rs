@@ -1,41 +1,299 @@
-use std::collections::HashMap;
+//! Population by age and sex, from GUS, and standard populations.
 
-use calamine::{open_workbook, Reader, Xls};
+use std::{collections::HashMap, fmt::Display, path::Path, str::FromStr};
 
-use crate::AgeGroup;
+use anyhow::anyhow;
+use calamine::{open_workbook, DataType, Reader, Xls};
+use regex::Regex;
+use serde::Deserialize;
 
-pub(crate) struct AgeDistribution {
-    ages: HashMap<usize, usize>,
+use crate::{errors::Location, regions::Voivodeship, AgeGroup, Stratum};
+
+/// Sex as reported by BASiW.
+#[derive(Debug, Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Sex {
+    #[serde(rename = "K")]
+    Female,
+    #[serde(rename = "M")]
+    Male,
+    #[serde(other)]
+    Unknown,
+}
+
+impl Sex {
+    /// Every sex which has its own population in GUS data.
+    pub const KNOWN: &'static [Sex] = &[Sex::Female, Sex::Male];
+
+    /// ASCII name, usable in file names.
+    pub fn slug(&self) -> &'static str {
+        match self {
+            Sex::Female => "kobiety",
+            Sex::Male => "mezczyzni",
+            Sex::Unknown => "nieznana",
+        }
+    }
+}
+
+impl Display for Sex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Sex::Female => write!(f, "kobiety"),
+            Sex::Male => write!(f, "mężczyźni"),
+            Sex::Unknown => write!(f, "płeć nieznana"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Population {
+    total: usize,
+    males: usize,
+    females: usize,
+}
+
+/// Population of Poland by sex and age, according to GUS.
+#[derive(Default)]
+pub struct AgeDistribution {
+    /// Population of every single year of age, and of the oldest people together.
+    ages: HashMap<AgeGroup, Population>,
+    /// Share of an age group living in a voivodeship. GUS table covers the whole country only.
+    regional_shares: HashMap<(Voivodeship, AgeGroup), f64>,
 }
 
 impl AgeDistribution {
-    pub(crate) fn population(&self) -> usize {
-        self.ages.values().sum()
+    /// Population of the whole country.
+    pub fn population(&self) -> usize {
+        self.ages.values().map(|population| population.total).sum()
+    }
+
+    pub(crate) fn with_regional_shares(
+        self,
+        regional_shares: HashMap<(Voivodeship, AgeGroup), f64>,
+    ) -> Self {
+        Self {
+            regional_shares,
+            ..self
+        }
     }
 
-    pub(crate) fn population_of(&self, age_group: AgeGroup) -> usize {
+    fn regional_share(&self, region: Voivodeship, age: usize) -> f64 {
+        self.regional_shares
+            .iter()
+            .find(|((voivodeship, age_group), _)| *voivodeship == region && age_group.includes(age))
+            .map(|(_, share)| *share)
+            .unwrap_or_default()
+    }
+
+    /// Population of an age group within a stratum. Regions get their share of it.
+    pub fn population_of(&self, age_group: AgeGroup, stratum: &Stratum) -> usize {
         self.ages
             .iter()
-            .map(|(age, count)| if age_group.includes(*age) { *count } else { 0 })
-            .sum()
+            .filter(|(ages, _)| age_group.contains(ages))
+            .map(|(ages, population)| {
+                let population = match stratum.sex {
+                    None => population.total,
+                    Some(Sex::Male) => population.males,
+                    Some(Sex::Female) => population.females,
+                    Some(Sex::Unknown) => 0,
+                };
+                match stratum.region {
+                    None => population as f64,
+                    Some(region) => population as f64 * self.regional_share(region, ages.from),
+                }
+            })
+            .sum::<f64>()
+            .round() as usize
+    }
+}
+
+/// European Standard Population 2013, by five year age bands.
+const EUROPEAN_STANDARD_POPULATION: &[(AgeGroup, usize)] = &[
+    (AgeGroup::new(0, 4), 5000),
+    (AgeGroup::new(5, 9), 5500),
+    (AgeGroup::new(10, 14), 5500),
+    (AgeGroup::new(15, 19), 5500),
+    (AgeGroup::new(20, 24), 6000),
+    (AgeGroup::new(25, 29), 6000),
+    (AgeGroup::new(30, 34), 6500),
+    (AgeGroup::new(35, 39), 7000),
+    (AgeGroup::new(40, 44), 7000),
+    (AgeGroup::new(45, 49), 7000),
+    (AgeGroup::new(50, 54), 7000),
+    (AgeGroup::new(55, 59), 6500),
+    (AgeGroup::new(60, 64), 6000),
+    (AgeGroup::new(65, 69), 5500),
+    (AgeGroup::new(70, 74), 5000),
+    (AgeGroup::new(75, 79), 4000),
+    (AgeGroup::open_ended(80), 5000),
+];
+
+/// Population age-standardized rates are weighted with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StandardPopulation {
+    /// Polish population from the GUS table.
+    #[default]
+    Gus,
+    /// European Standard Population 2013.
+    European,
+}
+
+impl StandardPopulation {
+    /// Weights of age groups, summing up to one. Five year bands of the European
+    /// Standard Population are split evenly between single years, its oldest band goes
+    /// to the group its first year belongs to.
+    pub fn weights(
+        &self,
+        ages: &AgeDistribution,
+        age_groups: &[AgeGroup],
+    ) -> HashMap<AgeGroup, f64> {
+        let population_of = |age_group: &AgeGroup| match self {
+            StandardPopulation::Gus => ages.population_of(*age_group, &Stratum::default()) as f64,
+            StandardPopulation::European => EUROPEAN_STANDARD_POPULATION
+                .iter()
+                .map(|(band, population)| match band.to {
+                    Some(to) => {
+                        let overlap = (band.from..=to)
+                            .filter(|age| age_group.includes(*age))
+                            .count();
+                        *population as f64 * overlap as f64 / (to - band.from + 1) as f64
+                    }
+                    None if age_group.includes(band.from) => *population as f64,
+                    None => 0.0,
+                })
+                .sum(),
+        };
+
+        let total: f64 = age_groups.iter().map(population_of).sum();
+        age_groups
+            .iter()
+            .map(|age_group| (*age_group, population_of(age_group) / total))
+            .collect()
+    }
+}
+
+impl Display for StandardPopulation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StandardPopulation::Gus => write!(f, "GUS 2021"),
+            StandardPopulation::European => write!(f, "ESP 2013"),
+        }
     }
 }
 
-pub(crate) fn age_distribution() -> AgeDistribution {
-    let mut workbook: Xls<_> = open_workbook("data/tabela01.xls").unwrap();
-    let range = workbook.worksheet_range("Tabl. 1").unwrap().unwrap();
-    let mut ages = HashMap::<usize, usize>::new();
+impl FromStr for StandardPopulation {
+    type Err = anyhow::Error;
 
-    for row in range.rows() {
-        if let Some(age) = row[0].get_float() {
-            let value: usize = match row[1] {
-                calamine::DataType::Int(x) => x as usize,
-                calamine::DataType::Float(x) => x as usize,
-                _ => panic!("can't interpret that"),
-            };
-            assert!(ages.insert(age as usize, value) == None);
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.to_lowercase().as_str() {
+            "gus" => Ok(StandardPopulation::Gus),
+            "esp" | "european" => Ok(StandardPopulation::European),
+            _ => Err(anyhow!(
+                "unknown standard population: '{}', expected gus or esp",
+                text
+            )),
+        }
+    }
+}
+
+/// Sheet of the GUS table with population by sex and single years of age.
+const SHEET: &str = "Tabl. 1";
+
+/// Error of the GUS table.
+#[derive(Debug, thiserror::Error)]
+pub enum PopulationError {
+    #[error("can't open {location}")]
+    Open {
+        location: Location,
+        source: calamine::XlsError,
+    },
+    #[error("{location}: missing sheet '{SHEET}'")]
+    MissingSheet { location: Location },
+    #[error("{location}: can't read sheet '{SHEET}'")]
+    Sheet {
+        location: Location,
+        source: calamine::XlsError,
+    },
+    #[error("{location}: expected a number, found {value}")]
+    BadCell { location: Location, value: String },
+    #[error("{location}: age {age_group} appears twice")]
+    DuplicateAge {
+        location: Location,
+        age_group: AgeGroup,
+    },
+}
+
+/// Reads population by sex and single years of age from a GUS table.
+pub fn age_distribution(path: &Path) -> Result<AgeDistribution, PopulationError> {
+    let mut workbook: Xls<_> = open_workbook(path).map_err(|source| PopulationError::Open {
+        location: Location::file(path),
+        source,
+    })?;
+    let range = workbook
+        .worksheet_range(SHEET)
+        .ok_or_else(|| PopulationError::MissingSheet {
+            location: Location::file(path),
+        })?
+        .map_err(|source| PopulationError::Sheet {
+            location: Location::file(path),
+            source,
+        })?;
+    let (first_row, first_column) = range.start().unwrap_or_default();
+    let location = |row: usize, column: usize| {
+        Location::cell(
+            path,
+            first_row as usize + row,
+            first_column as usize + column,
+        )
+    };
+    let cell_value = |row: usize, column: usize, cell: Option<&DataType>| match cell {
+        Some(DataType::Int(x)) => Ok(*x as usize),
+        Some(DataType::Float(x)) => Ok(*x as usize),
+        cell => Err(PopulationError::BadCell {
+            location: location(row, column),
+            value: cell.map_or("nothing".to_owned(), |cell| format!("{:?}", cell)),
+        }),
+    };
+
+    let mut ages = HashMap::<AgeGroup, Population>::new();
+    // Single years end at 84, everyone older is reported in a single "85 lat i więcej" row.
+    // Summary rows below it, like "65 lat i więcej", overlap single years and are skipped.
+    let oldest = Regex::new(r"^(\d+) lat i więcej").unwrap();
+
+    for (index, row) in range.rows().enumerate() {
+        let age_group = match row.first() {
+            Some(DataType::Float(age)) => AgeGroup::new(*age as usize, *age as usize),
+            Some(DataType::String(text)) => match oldest.captures(text.trim()) {
+                Some(captures) => match captures[1].parse() {
+                    Ok(from) => AgeGroup::open_ended(from),
+                    Err(_) => {
+                        return Err(PopulationError::BadCell {
+                            location: location(index, 0),
+                            value: text.clone(),
+                        })
+                    }
+                },
+                None => continue,
+            },
+            _ => continue,
+        };
+        let population = Population {
+            total: cell_value(index, 1, row.get(1))?,
+            males: cell_value(index, 2, row.get(2))?,
+            females: cell_value(index, 3, row.get(3))?,
+        };
+        if ages.insert(age_group, population).is_some() {
+            return Err(PopulationError::DuplicateAge {
+                location: location(index, 0),
+                age_group,
+            });
+        }
+        if age_group.to.is_none() {
+            break;
         }
     }
 
-    AgeDistribution { ages }
+    Ok(AgeDistribution {
+        ages,
+        regional_shares: HashMap::new(),
+    })
 }
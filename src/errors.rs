@@ -0,0 +1,110 @@
+//! Locations in source files errors of loaders refer to.
+
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+/// Place in a source file an error refers to. Lines and columns start at 1.
+#[derive(Debug, Clone)]
+pub struct Location {
+    /// Source file.
+    pub path: PathBuf,
+    /// Line of a CSV file, or row of a spreadsheet.
+    pub line: Option<u64>,
+    /// Field of a CSV record, or column of a spreadsheet.
+    pub column: Option<u64>,
+}
+
+impl Location {
+    pub(crate) fn file(path: &Path) -> Self {
+        Self {
+            path: path.to_owned(),
+            line: None,
+            column: None,
+        }
+    }
+
+    /// Cell of a spreadsheet, counting from 0 as calamine does.
+    pub(crate) fn cell(path: &Path, row: usize, column: usize) -> Self {
+        Self {
+            path: path.to_owned(),
+            line: Some(row as u64 + 1),
+            column: Some(column as u64 + 1),
+        }
+    }
+
+    /// Record (and field, if known) a CSV error happened at.
+    pub(crate) fn csv(path: &Path, error: &csv::Error) -> Self {
+        let column = match error.kind() {
+            csv::ErrorKind::Deserialize { err, .. } => err.field().map(|field| field + 1),
+            _ => None,
+        };
+        Self {
+            path: path.to_owned(),
+            line: error.position().map(|position| position.line()),
+            column,
+        }
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        if let Some(column) = self.column {
+            write!(f, ":{}", column)?;
+        }
+        Ok(())
+    }
+}
+
+/// Description of a CSV error without its position, which goes to the `Location`.
+pub(crate) fn message(error: &csv::Error) -> String {
+    match error.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => err.kind().to_string(),
+        _ => error.to_string(),
+    }
+}
+
+/// Value custom deserializers reject. csv keeps only the message of a custom error, so it
+/// names the kind of value alone, without the value itself, which the sample row shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub(crate) enum InvalidValue {
+    #[error("bad date")]
+    Date,
+    #[error("bad age")]
+    Age,
+    #[error("bad flag")]
+    Flag,
+    #[error("bad voivodeship")]
+    Voivodeship,
+    #[error("bad week")]
+    Week,
+    #[error("unknown target group")]
+    TargetGroup,
+    #[error("unknown region")]
+    Region,
+}
+
+impl InvalidValue {
+    /// Error of a deserializer.
+    pub(crate) fn error<E: serde::de::Error>(self) -> E {
+        E::custom(self)
+    }
+}
+
+/// Reason a CSV record failed to deserialize, common to every record failing alike. `None`
+/// for errors other than deserialization ones.
+pub(crate) fn reason(error: &csv::Error, headers: &csv::StringRecord) -> Option<String> {
+    match error.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => Some(match err.field() {
+            Some(field) => format!("bad value of {}", &headers[field as usize]),
+            // Custom errors don't know their field, but they're `InvalidValue`s.
+            None => err.kind().to_string(),
+        }),
+        _ => None,
+    }
+}
@@ -0,0 +1,515 @@
+//! Weekly reports in long format, one value per row, for other tools to consume.
+
+use std::{fmt::Display, fs::File, path::Path, str::FromStr, sync::Arc};
+
+use anyhow::{anyhow, Context};
+use parquet::{
+    data_type::{ByteArray, ByteArrayType, DoubleType},
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::parser::parse_message_type,
+};
+use serde::Serialize;
+
+use crate::{intervals::Estimate, WeeklyReport, WeeklyReports};
+
+/// File format of the long-format export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Comma separated values with a header.
+    Csv,
+    /// Array of objects.
+    Json,
+    /// Apache Parquet file with a single row group.
+    Parquet,
+}
+
+impl Format {
+    /// Every format.
+    pub const ALL: &'static [Format] = &[Format::Csv, Format::Json, Format::Parquet];
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Json => "json",
+            Format::Parquet => "parquet",
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Format::ALL
+            .iter()
+            .find(|format| text.eq_ignore_ascii_case(format.extension()))
+            .copied()
+            .ok_or_else(|| anyhow!("unknown format: '{}', expected csv, json or parquet", text))
+    }
+}
+
+/// Single value of a report: one metric of people with a given vaccination status.
+#[derive(Debug, Serialize)]
+pub struct Observation {
+    /// Week, e.g. `2021W27`.
+    pub week: String,
+    /// Age group, e.g. `80+`.
+    pub age_group: String,
+    /// Vaccination status: `unvaccinated`, `one_dose`, `two_doses` or `three_doses`.
+    pub status: &'static str,
+    /// Name of the metric, e.g. `deaths_per_million`.
+    pub metric: &'static str,
+    /// Value at full precision, `NaN` if undefined. JSON has no notation for it, nor for
+    /// infinite bounds of rate ratios, so it writes `null` for both.
+    pub value: f64,
+}
+
+/// Scale a metric's values are drawn on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scale {
+    /// Counts and shares.
+    Linear,
+    /// Rates and rate ratios, which span orders of magnitude.
+    Logarithmic,
+}
+
+/// Metric of people with a given vaccination status.
+type Metric = (&'static str, &'static str, Scale, fn(&WeeklyReport) -> f64);
+
+/// Share of the whole population of the report.
+fn share(people: usize, report: &WeeklyReport) -> f64 {
+    people as f64 / (report.unvaccinated_people + report.vaccinated_people.at_least_one_dose) as f64
+}
+
+/// Metrics available only for strata with population data.
+const RATES: &[Metric] = &[
+    ("unvaccinated", "population", Scale::Linear, |report| {
+        report.unvaccinated_people as f64
+    }),
+    ("one_dose", "population", Scale::Linear, |report| {
+        report.vaccinated_people.one_dose as f64
+    }),
+    ("two_doses", "population", Scale::Linear, |report| {
+        report.vaccinated_people.two_doses as f64
+    }),
+    ("three_doses", "population", Scale::Linear, |report| {
+        report.vaccinated_people.three_doses as f64
+    }),
+    ("unvaccinated", "coverage", Scale::Linear, |report| {
+        share(report.unvaccinated_people, report)
+    }),
+    ("one_dose", "coverage", Scale::Linear, |report| {
+        share(report.vaccinated_people.one_dose, report)
+    }),
+    ("two_doses", "coverage", Scale::Linear, |report| {
+        share(report.vaccinated_people.two_doses, report)
+    }),
+    ("three_doses", "coverage", Scale::Linear, |report| {
+        share(report.vaccinated_people.three_doses, report)
+    }),
+    ("unvaccinated", "person_weeks", Scale::Linear, |report| {
+        report.person_weeks.unvaccinated
+    }),
+    ("one_dose", "person_weeks", Scale::Linear, |report| {
+        report.person_weeks.one_dose
+    }),
+    ("two_doses", "person_weeks", Scale::Linear, |report| {
+        report.person_weeks.two_doses
+    }),
+    ("three_doses", "person_weeks", Scale::Linear, |report| {
+        report.person_weeks.three_doses
+    }),
+    ("unvaccinated", "cases", Scale::Linear, |report| {
+        report.absolute_cases.unvaccinated as f64
+    }),
+    ("one_dose", "cases", Scale::Linear, |report| {
+        report.absolute_cases.one_dose as f64
+    }),
+    ("two_doses", "cases", Scale::Linear, |report| {
+        report.absolute_cases.two_doses as f64
+    }),
+    ("three_doses", "cases", Scale::Linear, |report| {
+        report.absolute_cases.three_doses as f64
+    }),
+    (
+        "unvaccinated",
+        "cases_per_million",
+        Scale::Logarithmic,
+        |report| report.cases_per_million.unvaccinated,
+    ),
+    (
+        "one_dose",
+        "cases_per_million",
+        Scale::Logarithmic,
+        |report| report.cases_per_million.one_dose,
+    ),
+    (
+        "two_doses",
+        "cases_per_million",
+        Scale::Logarithmic,
+        |report| report.cases_per_million.two_doses,
+    ),
+    (
+        "three_doses",
+        "cases_per_million",
+        Scale::Logarithmic,
+        |report| report.cases_per_million.three_doses,
+    ),
+    (
+        "unvaccinated",
+        "cases_per_100k_person_weeks",
+        Scale::Logarithmic,
+        |report| report.cases_per_100k_person_weeks.unvaccinated,
+    ),
+    (
+        "one_dose",
+        "cases_per_100k_person_weeks",
+        Scale::Logarithmic,
+        |report| report.cases_per_100k_person_weeks.one_dose,
+    ),
+    (
+        "two_doses",
+        "cases_per_100k_person_weeks",
+        Scale::Logarithmic,
+        |report| report.cases_per_100k_person_weeks.two_doses,
+    ),
+    (
+        "three_doses",
+        "cases_per_100k_person_weeks",
+        Scale::Logarithmic,
+        |report| report.cases_per_100k_person_weeks.three_doses,
+    ),
+    (
+        "unvaccinated",
+        "deaths_per_million",
+        Scale::Logarithmic,
+        |report| report.deaths_per_million.unvaccinated,
+    ),
+    (
+        "one_dose",
+        "deaths_per_million",
+        Scale::Logarithmic,
+        |report| report.deaths_per_million.one_dose,
+    ),
+    (
+        "two_doses",
+        "deaths_per_million",
+        Scale::Logarithmic,
+        |report| report.deaths_per_million.two_doses,
+    ),
+    (
+        "three_doses",
+        "deaths_per_million",
+        Scale::Logarithmic,
+        |report| report.deaths_per_million.three_doses,
+    ),
+    (
+        "unvaccinated",
+        "deaths_per_100k_person_weeks",
+        Scale::Logarithmic,
+        |report| report.deaths_per_100k_person_weeks.unvaccinated,
+    ),
+    (
+        "one_dose",
+        "deaths_per_100k_person_weeks",
+        Scale::Logarithmic,
+        |report| report.deaths_per_100k_person_weeks.one_dose,
+    ),
+    (
+        "two_doses",
+        "deaths_per_100k_person_weeks",
+        Scale::Logarithmic,
+        |report| report.deaths_per_100k_person_weeks.two_doses,
+    ),
+    (
+        "three_doses",
+        "deaths_per_100k_person_weeks",
+        Scale::Logarithmic,
+        |report| report.deaths_per_100k_person_weeks.three_doses,
+    ),
+    (
+        "unvaccinated",
+        "cfr",
+        Scale::Linear,
+        WeeklyReport::cfr_unvaccinated,
+    ),
+    ("one_dose", "cfr", Scale::Linear, WeeklyReport::cfr_one_dose),
+    (
+        "two_doses",
+        "cfr",
+        Scale::Linear,
+        WeeklyReport::cfr_two_doses,
+    ),
+    (
+        "three_doses",
+        "cfr",
+        Scale::Linear,
+        WeeklyReport::cfr_three_doses,
+    ),
+];
+
+/// Rate ratio against the unvaccinated, along with bounds of its confidence interval.
+type RateRatio = (
+    &'static str,
+    [&'static str; 3],
+    Scale,
+    fn(&WeeklyReport) -> Estimate,
+);
+
+const DEATH_RATE_RATIO: [&str; 3] = [
+    "death_rate_ratio",
+    "death_rate_ratio_lower",
+    "death_rate_ratio_upper",
+];
+const CASE_RATE_RATIO: [&str; 3] = [
+    "case_rate_ratio",
+    "case_rate_ratio_lower",
+    "case_rate_ratio_upper",
+];
+
+const RATE_RATIOS: &[RateRatio] = &[
+    (
+        "one_dose",
+        DEATH_RATE_RATIO,
+        Scale::Logarithmic,
+        WeeklyReport::risk_ratio_of_one_dose,
+    ),
+    (
+        "two_doses",
+        DEATH_RATE_RATIO,
+        Scale::Logarithmic,
+        WeeklyReport::risk_ratio_of_two_doses,
+    ),
+    (
+        "three_doses",
+        DEATH_RATE_RATIO,
+        Scale::Logarithmic,
+        WeeklyReport::risk_ratio_of_three_doses,
+    ),
+    (
+        "one_dose",
+        CASE_RATE_RATIO,
+        Scale::Logarithmic,
+        WeeklyReport::case_risk_ratio_of_one_dose,
+    ),
+    (
+        "two_doses",
+        CASE_RATE_RATIO,
+        Scale::Logarithmic,
+        WeeklyReport::case_risk_ratio_of_two_doses,
+    ),
+    (
+        "three_doses",
+        CASE_RATE_RATIO,
+        Scale::Logarithmic,
+        WeeklyReport::case_risk_ratio_of_three_doses,
+    ),
+];
+
+const DEATHS: &[Metric] = &[
+    ("unvaccinated", "deaths", Scale::Linear, |report| {
+        report.absolute_deaths.unvaccinated as f64
+    }),
+    ("one_dose", "deaths", Scale::Linear, |report| {
+        report.absolute_deaths.one_dose as f64
+    }),
+    ("two_doses", "deaths", Scale::Linear, |report| {
+        report.absolute_deaths.two_doses as f64
+    }),
+    ("three_doses", "deaths", Scale::Linear, |report| {
+        report.absolute_deaths.three_doses as f64
+    }),
+];
+
+/// Status, metric and value of every metric of a report, in the order they are written.
+/// Without population data only deaths are known.
+fn values(report: &WeeklyReport, with_rates: bool) -> Vec<(&'static str, &'static str, f64)> {
+    let mut values: Vec<_> = DEATHS
+        .iter()
+        .map(|(status, metric, _, value)| (*status, *metric, value(report)))
+        .collect();
+    if !with_rates {
+        return values;
+    }
+    for (status, metric, _, value) in RATES {
+        values.push((status, metric, value(report)));
+    }
+    for (status, [value, lower, upper], _, estimate) in RATE_RATIOS {
+        let estimate = estimate(report);
+        values.push((status, value, estimate.value));
+        values.push((status, lower, estimate.lower));
+        values.push((status, upper, estimate.upper));
+    }
+    values
+}
+
+/// Every value of the reports at full precision, one per row. Strata without population
+/// data only come with deaths.
+pub fn observations(reports: &WeeklyReports) -> Vec<Observation> {
+    let mut observations = Vec::new();
+    for (week, by_age_group) in &reports.weeks {
+        for age_group in &reports.age_groups {
+            let report = &by_age_group[age_group];
+            for (status, metric, value) in values(report, reports.stratum.has_denominator()) {
+                observations.push(Observation {
+                    week: week.to_string(),
+                    age_group: age_group.to_string(),
+                    status,
+                    metric,
+                    value,
+                });
+            }
+        }
+    }
+    observations
+}
+
+/// Metric of people with a given vaccination status as named in the long format, written
+/// as `metric:status`, e.g. `deaths_per_million:unvaccinated`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetricName {
+    /// Name of the metric, e.g. `deaths_per_million`.
+    pub metric: &'static str,
+    /// Vaccination status, e.g. `unvaccinated`.
+    pub status: &'static str,
+}
+
+impl MetricName {
+    /// Every metric written in long format.
+    pub fn all() -> impl Iterator<Item = MetricName> {
+        let metrics = DEATHS
+            .iter()
+            .chain(RATES)
+            .map(|(status, metric, _, _)| MetricName { metric, status });
+        let rate_ratios = RATE_RATIOS.iter().flat_map(|(status, metrics, _, _)| {
+            metrics.map(|metric| MetricName { metric, status })
+        });
+        metrics.chain(rate_ratios)
+    }
+
+    /// Scale of the metric, as defined along with it.
+    pub fn scale(&self) -> Scale {
+        let metrics = DEATHS
+            .iter()
+            .chain(RATES)
+            .map(|(status, metric, scale, _)| (*status, *metric, *scale));
+        let rate_ratios = RATE_RATIOS.iter().flat_map(|(status, metrics, scale, _)| {
+            metrics.map(|metric| (*status, metric, *scale))
+        });
+        metrics
+            .chain(rate_ratios)
+            .find(|(status, metric, _)| *status == self.status && *metric == self.metric)
+            .map_or(Scale::Linear, |(_, _, scale)| scale)
+    }
+
+    /// Value of the metric in a report, `NaN` if undefined.
+    pub fn value(&self, report: &WeeklyReport) -> f64 {
+        values(report, true)
+            .into_iter()
+            .find(|(status, metric, _)| *status == self.status && *metric == self.metric)
+            .map_or(f64::NAN, |(_, _, value)| value)
+    }
+}
+
+impl Display for MetricName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.metric, self.status)
+    }
+}
+
+impl FromStr for MetricName {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        MetricName::all()
+            .find(|name| name.to_string() == text)
+            .ok_or_else(|| {
+                anyhow!(
+                    "unknown metric: '{}', expected e.g. deaths_per_million:unvaccinated",
+                    text
+                )
+            })
+    }
+}
+
+/// Writes reports in long format as `weekly_reports.{csv,json,parquet}`.
+pub fn write(reports: &WeeklyReports, format: Format, output: &Path) -> anyhow::Result<()> {
+    let path = output.join(format!("weekly_reports.{}", format.extension()));
+    let file = File::create(&path).with_context(|| format!("can't create {}", path.display()))?;
+    let observations = observations(reports);
+    match format {
+        Format::Csv => write_csv(&observations, file),
+        Format::Json => serde_json::to_writer(file, &observations).map_err(Into::into),
+        Format::Parquet => write_parquet(&observations, file),
+    }
+    .with_context(|| format!("can't write {}", path.display()))
+}
+
+fn write_csv(observations: &[Observation], file: File) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_writer(file);
+    for observation in observations {
+        writer.serialize(observation)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+const PARQUET_SCHEMA: &str = "
+    message weekly_reports {
+        required binary week (UTF8);
+        required binary age_group (UTF8);
+        required binary status (UTF8);
+        required binary metric (UTF8);
+        required double value;
+    }
+";
+
+/// Writes observations as a single row group, they are small enough.
+fn write_parquet(observations: &[Observation], file: File) -> anyhow::Result<()> {
+    let schema = Arc::new(parse_message_type(PARQUET_SCHEMA)?);
+    let properties = Arc::new(WriterProperties::builder().build());
+    let mut writer = SerializedFileWriter::new(file, schema, properties)?;
+    let mut row_group = writer.next_row_group()?;
+
+    let text_columns: [fn(&Observation) -> &str; 4] = [
+        |observation| &observation.week,
+        |observation| &observation.age_group,
+        |observation| observation.status,
+        |observation| observation.metric,
+    ];
+    for column in text_columns {
+        let values: Vec<ByteArray> = observations
+            .iter()
+            .map(|observation| column(observation).into())
+            .collect();
+        let mut writer = row_group
+            .next_column()?
+            .context("parquet schema is missing a column")?;
+        writer
+            .typed::<ByteArrayType>()
+            .write_batch(&values, None, None)?;
+        writer.close()?;
+    }
+
+    let values: Vec<f64> = observations
+        .iter()
+        .map(|observation| observation.value)
+        .collect();
+    let mut values_writer = row_group
+        .next_column()?
+        .context("parquet schema is missing a column")?;
+    values_writer
+        .typed::<DoubleType>()
+        .write_batch(&values, None, None)?;
+    values_writer.close()?;
+
+    row_group.close()?;
+    writer.close()?;
+    Ok(())
+}
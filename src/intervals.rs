@@ -0,0 +1,231 @@
+//! Confidence intervals of rate ratios, of single age groups and pooled over them.
+
+use statrs::{
+    distribution::{ContinuousCDF, Normal},
+    function::beta::inv_beta_reg,
+};
+
+/// Confidence level of every interval.
+const CONFIDENCE: f64 = 0.95;
+
+/// Point estimate along with its confidence interval.
+#[derive(Debug, Clone, Copy)]
+pub struct Estimate {
+    /// Point estimate.
+    pub value: f64,
+    /// Lower bound of the confidence interval.
+    pub lower: f64,
+    /// Upper bound of the confidence interval.
+    pub upper: f64,
+}
+
+impl Estimate {
+    /// Estimate which can't be made, e.g. a rate ratio without any events.
+    pub const UNDEFINED: Estimate = Estimate {
+        value: f64::NAN,
+        lower: f64::NAN,
+        upper: f64::NAN,
+    };
+
+    /// Vaccine effectiveness, `1 - RR`, of a risk ratio. Bounds swap places.
+    pub fn effectiveness(&self) -> Estimate {
+        Estimate {
+            value: 1.0 - self.value,
+            lower: 1.0 - self.upper,
+            upper: 1.0 - self.lower,
+        }
+    }
+
+    /// The same estimate in percent.
+    pub fn percent(&self) -> Estimate {
+        Estimate {
+            value: self.value * 100.0,
+            lower: self.lower * 100.0,
+            upper: self.upper * 100.0,
+        }
+    }
+
+    /// Bounds of the interval, e.g. `0.21–0.35`. The dash keeps negative bounds readable.
+    /// An undefined interval is a lone dash.
+    pub fn interval(&self) -> String {
+        if self.lower.is_nan() || self.upper.is_nan() {
+            return "–".to_owned();
+        }
+        format!("{:.2}–{:.2}", self.lower, self.upper)
+    }
+}
+
+/// Events (deaths, infections) of a group, e.g. vaccinated with two doses, compared against
+/// a reference group, usually the unvaccinated.
+#[derive(Debug, Clone, Copy)]
+pub struct Comparison {
+    /// Events of the group.
+    pub events: usize,
+    /// Person-weeks of the group, or people if the events are of a single week.
+    pub population: f64,
+    /// Events of the reference group.
+    pub reference_events: usize,
+    /// Person-weeks (or people) of the reference group.
+    pub reference_population: f64,
+}
+
+impl Comparison {
+    /// Ratio of incidence rates, with an exact Poisson interval. Given the total number of
+    /// events, the ones of the first group follow a binomial distribution, so the
+    /// Clopper-Pearson interval of their share is transformed into an interval of the ratio.
+    /// Unlike the log-normal one, it stays meaningful for a handful of deaths, or none.
+    pub fn rate_ratio(&self) -> Estimate {
+        let value = (self.events as f64 / self.population)
+            / (self.reference_events as f64 / self.reference_population);
+        let total = self.events + self.reference_events;
+        if total == 0 || self.population == 0.0 || self.reference_population == 0.0 {
+            return Estimate {
+                value,
+                ..Estimate::UNDEFINED
+            };
+        }
+
+        let alpha = 1.0 - CONFIDENCE;
+        let (events, total) = (self.events as f64, total as f64);
+        let lower_share = if events == 0.0 {
+            0.0
+        } else {
+            inv_beta_reg(events, total - events + 1.0, alpha / 2.0)
+        };
+        let upper_share = if events == total {
+            1.0
+        } else {
+            inv_beta_reg(events + 1.0, total - events, 1.0 - alpha / 2.0)
+        };
+        let ratio_of =
+            |share: f64| share / (1.0 - share) * self.reference_population / self.population;
+
+        Estimate {
+            value,
+            lower: ratio_of(lower_share),
+            upper: ratio_of(upper_share),
+        }
+    }
+}
+
+/// Mantel-Haenszel rate ratio pooled over strata (age groups), with the Greenland-Robins
+/// variance of its logarithm. Strata without any events or population add nothing to it.
+/// Without events on one side the logarithm has no variance, so the interval is one-sided:
+/// its open bound is the exact one of the strata summed up. Without any events at all the
+/// estimate is undefined.
+pub fn mantel_haenszel(comparisons: impl Iterator<Item = Comparison>) -> Estimate {
+    mantel_haenszel_of_shares(comparisons.map(|comparison| (comparison, 1.0)))
+}
+
+/// Mantel-Haenszel rate ratio of a share of every stratum's group, e.g. of the people
+/// vaccinated some weeks ago, assuming the share has the rate of the whole group. Its
+/// events and population are scaled by the share, the reference group is kept whole.
+pub fn mantel_haenszel_of_shares(comparisons: impl Iterator<Item = (Comparison, f64)>) -> Estimate {
+    let (mut numerator, mut denominator, mut variance) = (0.0, 0.0, 0.0);
+    let (mut events, mut population, mut reference_events, mut reference_population) =
+        (0.0, 0.0, 0, 0.0);
+    for (comparison, share) in comparisons {
+        let exposed_events = comparison.events as f64 * share;
+        let exposed = comparison.population * share;
+        let reference = comparison.reference_population;
+        let total = exposed + reference;
+        if total == 0.0 {
+            continue;
+        }
+        numerator += exposed_events * reference / total;
+        denominator += comparison.reference_events as f64 * exposed / total;
+        variance += exposed * reference * (exposed_events + comparison.reference_events as f64)
+            / total.powi(2);
+        events += exposed_events;
+        population += exposed;
+        reference_events += comparison.reference_events;
+        reference_population += reference;
+    }
+    let crude = Comparison {
+        events: events.round() as usize,
+        population,
+        reference_events,
+        reference_population,
+    };
+
+    let value = numerator / denominator;
+    match (numerator > 0.0, denominator > 0.0) {
+        (false, false) => return Estimate::UNDEFINED,
+        (false, true) => {
+            return Estimate {
+                value,
+                lower: 0.0,
+                upper: crude.rate_ratio().upper,
+            }
+        }
+        (true, false) => {
+            return Estimate {
+                value,
+                lower: crude.rate_ratio().lower,
+                upper: f64::INFINITY,
+            }
+        }
+        (true, true) => {}
+    }
+    let error = z_score() * (variance / (numerator * denominator)).sqrt();
+    Estimate {
+        value,
+        lower: (value.ln() - error).exp(),
+        upper: (value.ln() + error).exp(),
+    }
+}
+
+/// Quantile of the standard normal distribution for a two-sided interval.
+fn z_score() -> f64 {
+    Normal::new(0.0, 1.0)
+        .unwrap()
+        .inverse_cdf(1.0 - (1.0 - CONFIDENCE) / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comparison(events: usize, reference_events: usize) -> Comparison {
+        Comparison {
+            events,
+            population: 1_000.0,
+            reference_events,
+            reference_population: 1_000.0,
+        }
+    }
+
+    #[test]
+    fn no_exposed_events_bound_the_pooled_ratio_from_above() {
+        let estimate = mantel_haenszel([comparison(0, 4), comparison(0, 6)].into_iter());
+        assert_eq!(estimate.value, 0.0);
+        assert_eq!(estimate.lower, 0.0);
+        assert!(estimate.upper.is_finite() && estimate.upper > 0.0);
+        assert_eq!(estimate.upper, comparison(0, 10).rate_ratio().upper);
+    }
+
+    #[test]
+    fn no_reference_events_bound_the_pooled_ratio_from_below() {
+        let estimate = mantel_haenszel([comparison(4, 0), comparison(6, 0)].into_iter());
+        assert_eq!(estimate.value, f64::INFINITY);
+        assert_eq!(estimate.upper, f64::INFINITY);
+        assert!(estimate.lower.is_finite() && estimate.lower > 0.0);
+        assert_eq!(estimate.interval(), format!("{:.2}–inf", estimate.lower));
+    }
+
+    #[test]
+    fn no_events_at_all_leave_the_pooled_ratio_undefined() {
+        let estimate = mantel_haenszel([comparison(0, 0)].into_iter());
+        assert!(estimate.value.is_nan());
+        assert_eq!(estimate.interval(), "–");
+    }
+
+    #[test]
+    fn shares_of_a_group_keep_its_rate_ratio() {
+        let whole = mantel_haenszel([comparison(4, 8)].into_iter());
+        let quarter = mantel_haenszel_of_shares([(comparison(4, 8), 0.25)].into_iter());
+        assert!((quarter.value - whole.value).abs() < 1e-12);
+        // A smaller group is a weaker evidence.
+        assert!(quarter.upper - quarter.lower > whole.upper - whole.lower);
+    }
+}
@@ -0,0 +1,982 @@
+//! Effectiveness of COVID-19 vaccination in Poland.
+//!
+//! Deaths and infections by vaccination status come from BASiW ([`deaths`]), vaccinated
+//! people from ECDC ([`vaccination`]) and population by age from GUS ([`demographics`]).
+//! [`datasets::Datasets`] loads all of them and turns them into [`WeeklyReports`]: deaths,
+//! infections and their rates of every week and age group, which [`tables`], [`plots`]
+//! and [`export`] present.
+//!
+//! Reports can also be built out of data already in memory:
+//!
+//! ```
+//! use covid_vaccine_efficacy::{
+//!     vaccination::VaccinatedPeople, AgeGroup, DeathRate, WeeklyReport, WeeklyReports,
+//!     YearWeek,
+//! };
+//!
+//! let vaccinated_people = VaccinatedPeople {
+//!     two_doses: 800_000,
+//!     ..VaccinatedPeople::default()
+//! };
+//! let deaths = DeathRate {
+//!     unvaccinated: 40,
+//!     one_dose: 0,
+//!     two_doses: 8,
+//!     three_doses: 0,
+//! };
+//! let cases = DeathRate {
+//!     unvaccinated: 2000,
+//!     one_dose: 0,
+//!     two_doses: 1600,
+//!     three_doses: 0,
+//! };
+//! let report = WeeklyReport::new(vaccinated_people, 200_000, cases, deaths);
+//!
+//! let reports = WeeklyReports::builder()
+//!     .report(YearWeek((2021, 45)), AgeGroup::open_ended(80), report)
+//!     .build()?;
+//! let (_, rr) = reports.pooled(WeeklyReport::deaths_of_two_doses)[0];
+//! assert!((rr.value - 0.05).abs() < 1e-9);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    hash::Hash,
+    ops::Add,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Context};
+use chrono::{Datelike, Duration, IsoWeek, NaiveDate, Weekday};
+use demographics::{AgeDistribution, Sex, StandardPopulation};
+use intervals::{mantel_haenszel, Comparison, Estimate};
+use regions::Voivodeship;
+use vaccination::{Product, VaccinatedPeople};
+
+pub mod datasets;
+pub mod deaths;
+pub mod demographics;
+pub mod errors;
+pub mod export;
+pub mod intervals;
+pub mod plots;
+pub mod quality;
+pub mod regions;
+pub mod sensitivity;
+pub mod snapshots;
+pub mod tables;
+pub mod vaccination;
+pub mod waning;
+
+/// ISO week of a year, e.g. `2021W27`.
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct YearWeek(pub (u32, u32));
+
+impl YearWeek {
+    /// Monday the week starts with, `None` if there is no such week.
+    pub fn monday(&self) -> Option<NaiveDate> {
+        NaiveDate::from_isoywd_opt(self.0 .0 as i32, self.0 .1, Weekday::Mon)
+    }
+
+    /// Whole weeks since an earlier week, zero for the same one. `None` if the other week
+    /// is later, or either of them doesn't exist.
+    pub fn weeks_since(&self, earlier: YearWeek) -> Option<usize> {
+        let days = (self.monday()? - earlier.monday()?).num_days();
+        usize::try_from(days / 7).ok().filter(|_| days >= 0)
+    }
+
+    /// Week a given number of weeks earlier, `None` if this week doesn't exist.
+    pub fn weeks_before(&self, weeks: usize) -> Option<YearWeek> {
+        let monday = self.monday()? - Duration::weeks(weeks as i64);
+        Some(YearWeek::from(monday.iso_week()))
+    }
+}
+
+impl From<IsoWeek> for YearWeek {
+    fn from(week: IsoWeek) -> Self {
+        YearWeek((week.year() as u32, week.week()))
+    }
+}
+
+impl Display for YearWeek {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}W{:02}", self.0 .0, self.0 .1)
+    }
+}
+
+impl FromStr for YearWeek {
+    type Err = anyhow::Error;
+
+    /// Accepts both our own `2021W05` and ECDC's `2021-W05` notation.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (year, week) = text
+            .split_once("-W")
+            .or_else(|| text.split_once('W'))
+            .ok_or_else(|| anyhow!("bad week: '{}', expected e.g. 2021W05", text))?;
+        Ok(YearWeek((
+            year.parse()
+                .with_context(|| format!("bad year in '{}'", text))?,
+            week.parse()
+                .with_context(|| format!("bad week in '{}'", text))?,
+        )))
+    }
+}
+
+/// Every week from `min` to `max`, as long as they are within the data, starting with 2021.
+pub fn weeks(min: YearWeek, max: YearWeek) -> impl Iterator<Item = YearWeek> {
+    NaiveDate::from_ymd_opt(2021, 1, 1)
+        .unwrap()
+        .iter_weeks()
+        .map(|week| YearWeek::from(week.iso_week()))
+        .skip_while(move |week| week < &min)
+        .take_while(move |week| week <= &max)
+}
+
+/// Range of ages, both ends included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AgeGroup {
+    from: usize,
+    /// `None` for the oldest group, e.g. 80+.
+    to: Option<usize>,
+}
+
+impl AgeGroup {
+    /// Ages from `from` to `to`.
+    pub const fn new(from: usize, to: usize) -> Self {
+        Self { from, to: Some(to) }
+    }
+
+    /// Ages from `from` up.
+    pub const fn open_ended(from: usize) -> Self {
+        Self { from, to: None }
+    }
+
+    /// Whether the age belongs to this group.
+    pub fn includes(&self, age: usize) -> bool {
+        age >= self.from && self.to.is_none_or(|to| age <= to)
+    }
+
+    /// Whether every age of the other group belongs to this one.
+    pub fn contains(&self, other: &AgeGroup) -> bool {
+        self.includes(other.from)
+            && match (self.to, other.to) {
+                (None, _) => true,
+                (Some(_), None) => false,
+                (Some(to), Some(other_to)) => other_to <= to,
+            }
+    }
+
+    /// ASCII name, usable in file names.
+    pub fn slug(&self) -> String {
+        match self.to {
+            Some(to) => format!("{}_{}", self.from, to),
+            None => format!("{}_plus", self.from),
+        }
+    }
+}
+
+impl Display for AgeGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.to {
+            Some(to) => write!(f, "{} - {}", self.from, to),
+            None => write!(f, "{}+", self.from),
+        }
+    }
+}
+
+impl FromStr for AgeGroup {
+    type Err = anyhow::Error;
+
+    /// Only groups from `AGE_GROUPS` are accepted, since vaccination data can't be
+    /// regrouped.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let age_group = match text.trim().strip_suffix('+') {
+            Some(from) => AgeGroup::open_ended(from.trim().parse()?),
+            None => {
+                let (from, to) = text
+                    .split_once('-')
+                    .ok_or_else(|| anyhow!("bad age group: '{}', expected e.g. 50-59", text))?;
+                AgeGroup::new(from.trim().parse()?, to.trim().parse()?)
+            }
+        };
+        AGE_GROUPS
+            .iter()
+            .find(|known| **known == age_group)
+            .copied()
+            .ok_or_else(|| {
+                anyhow!(
+                    "unknown age group: '{}', known ones are: {}",
+                    text,
+                    AGE_GROUPS
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    }
+}
+
+/// Subset of the population a report is calculated for. `None` stands for every value,
+/// so the default stratum is the whole population.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Stratum {
+    /// Sex, both of them if `None`.
+    pub sex: Option<Sex>,
+    /// Voivodeship, the whole country if `None`.
+    pub region: Option<Voivodeship>,
+    /// Whether the dead had comorbidities.
+    pub comorbidities: Option<bool>,
+    /// Whether the dead had reduced immunity.
+    pub reduced_immunity: Option<bool>,
+    /// Vaccine the vaccinated got, any of them if `None`.
+    pub product: Option<Product>,
+}
+
+impl Stratum {
+    /// Whether a record, whose stratum has every field it knows about set, belongs to this one.
+    pub fn includes(&self, record: &Stratum) -> bool {
+        self.sex.is_none_or(|sex| record.sex == Some(sex))
+            && self
+                .region
+                .is_none_or(|region| record.region == Some(region))
+            && self
+                .comorbidities
+                .is_none_or(|comorbidities| record.comorbidities == Some(comorbidities))
+            && self
+                .reduced_immunity
+                .is_none_or(|reduced_immunity| record.reduced_immunity == Some(reduced_immunity))
+            && self
+                .product
+                .is_none_or(|product| record.product == Some(product))
+    }
+
+    /// Population data exist only for sexes and regions. For other strata only deaths
+    /// can be reported.
+    pub fn has_denominator(&self) -> bool {
+        self.comorbidities.is_none() && self.reduced_immunity.is_none()
+    }
+
+    /// Subdirectory of the output where results of this stratum are written.
+    pub fn slug(&self) -> Option<String> {
+        let parts: Vec<String> = [
+            self.sex.map(|sex| sex.slug().to_owned()),
+            self.region.map(|region| region.nuts().to_owned()),
+            self.comorbidities.map(|comorbidities| match comorbidities {
+                true => "wspolistniejace".to_owned(),
+                false => "bez_wspolistniejacych".to_owned(),
+            }),
+            self.reduced_immunity
+                .map(|reduced_immunity| match reduced_immunity {
+                    true => "obnizona_odpornosc".to_owned(),
+                    false => "bez_obnizonej_odpornosci".to_owned(),
+                }),
+            self.product.map(|product| product.slug().to_owned()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        (!parts.is_empty()).then(|| parts.join("_"))
+    }
+}
+
+impl Display for Stratum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts: Vec<String> = [
+            self.sex.map(|sex| sex.to_string()),
+            self.region.map(|region| format!("woj. {}", region)),
+            self.comorbidities.map(|comorbidities| match comorbidities {
+                true => "z chorobami współistniejącymi".to_owned(),
+                false => "bez chorób współistniejących".to_owned(),
+            }),
+            self.reduced_immunity
+                .map(|reduced_immunity| match reduced_immunity {
+                    true => "z obniżoną odpornością".to_owned(),
+                    false => "bez obniżonej odporności".to_owned(),
+                }),
+            self.product
+                .map(|product| format!("szczepionka {}", product)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if parts.is_empty() {
+            write!(f, "ogółem")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
+fn per_million(nominator: usize, denominator: usize) -> f64 {
+    nominator as f64 * 1_000_000_f64 / denominator as f64
+}
+
+fn per_100k(nominator: usize, person_weeks: f64) -> f64 {
+    nominator as f64 * 100_000_f64 / person_weeks
+}
+
+/// Value of every vaccination status, e.g. deaths or deaths per million.
+#[derive(Debug, Clone, Copy)]
+pub struct DeathRate<T> {
+    /// People who didn't get any dose.
+    pub unvaccinated: T,
+    /// People who got the first dose of a two-dose vaccine only.
+    pub one_dose: T,
+    /// People fully vaccinated (two doses, or one of Johnson&Johnson), without a booster.
+    pub two_doses: T,
+    /// People who got a booster.
+    pub three_doses: T,
+}
+
+impl<T: Add<Output = T> + Copy> DeathRate<T> {
+    /// Sum over every vaccination status.
+    pub fn total(&self) -> T {
+        self.unvaccinated + self.one_dose + self.two_doses + self.three_doses
+    }
+}
+
+/// Deaths and infections of a single week and age group by vaccination status, along with
+/// the population they come from.
+#[derive(Debug, Clone, Copy)]
+pub struct WeeklyReport {
+    /// People by number of doses they got.
+    pub vaccinated_people: VaccinatedPeople,
+    /// People who didn't get any dose.
+    pub unvaccinated_people: usize,
+    /// Infections.
+    pub absolute_cases: DeathRate<usize>,
+    /// Deaths.
+    pub absolute_deaths: DeathRate<usize>,
+    /// Deaths per million people of each status.
+    pub deaths_per_million: DeathRate<f64>,
+    /// Infections per million people of each status.
+    pub cases_per_million: DeathRate<f64>,
+    /// Person-weeks lived with each status during the week, or the people at its end if
+    /// uptake within the week is unknown.
+    pub person_weeks: DeathRate<f64>,
+    /// Deaths per 100 000 person-weeks of each status.
+    pub deaths_per_100k_person_weeks: DeathRate<f64>,
+    /// Infections per 100 000 person-weeks of each status.
+    pub cases_per_100k_person_weeks: DeathRate<f64>,
+}
+
+fn per_100k_of(events: DeathRate<usize>, person_weeks: DeathRate<f64>) -> DeathRate<f64> {
+    DeathRate {
+        unvaccinated: per_100k(events.unvaccinated, person_weeks.unvaccinated),
+        one_dose: per_100k(events.one_dose, person_weeks.one_dose),
+        two_doses: per_100k(events.two_doses, person_weeks.two_doses),
+        three_doses: per_100k(events.three_doses, person_weeks.three_doses),
+    }
+}
+
+impl WeeklyReport {
+    /// Report of given people and their infections and deaths. Rates per million are
+    /// computed out of them.
+    pub fn new(
+        vaccinated_people: VaccinatedPeople,
+        unvaccinated_people: usize,
+        absolute_cases: DeathRate<usize>,
+        absolute_deaths: DeathRate<usize>,
+    ) -> Self {
+        let per_million_of = |events: DeathRate<usize>| DeathRate {
+            unvaccinated: per_million(events.unvaccinated, unvaccinated_people),
+            one_dose: per_million(events.one_dose, vaccinated_people.one_dose),
+            two_doses: per_million(events.two_doses, vaccinated_people.two_doses),
+            three_doses: per_million(events.three_doses, vaccinated_people.three_doses),
+        };
+
+        let people = DeathRate {
+            unvaccinated: unvaccinated_people as f64,
+            one_dose: vaccinated_people.one_dose as f64,
+            two_doses: vaccinated_people.two_doses as f64,
+            three_doses: vaccinated_people.three_doses as f64,
+        };
+
+        Self {
+            vaccinated_people,
+            unvaccinated_people,
+            absolute_cases,
+            absolute_deaths,
+            deaths_per_million: per_million_of(absolute_deaths),
+            cases_per_million: per_million_of(absolute_cases),
+            person_weeks: people,
+            deaths_per_100k_person_weeks: per_100k_of(absolute_deaths, people),
+            cases_per_100k_person_weeks: per_100k_of(absolute_cases, people),
+        }
+    }
+
+    /// Report with person-weeks of each status, e.g. interpolating uptake within the week.
+    /// Rate ratios and rates per 100 000 person-weeks are computed out of them.
+    pub fn with_person_weeks(self, person_weeks: DeathRate<f64>) -> Self {
+        Self {
+            person_weeks,
+            deaths_per_100k_person_weeks: per_100k_of(self.absolute_deaths, person_weeks),
+            cases_per_100k_person_weeks: per_100k_of(self.absolute_cases, person_weeks),
+            ..self
+        }
+    }
+
+    /// Deaths of people with a single dose compared against the unvaccinated.
+    pub fn deaths_of_one_dose(&self) -> Comparison {
+        Comparison {
+            events: self.absolute_deaths.one_dose,
+            population: self.person_weeks.one_dose,
+            reference_events: self.absolute_deaths.unvaccinated,
+            reference_population: self.person_weeks.unvaccinated,
+        }
+    }
+
+    /// Deaths of people with two doses compared against the unvaccinated.
+    pub fn deaths_of_two_doses(&self) -> Comparison {
+        Comparison {
+            events: self.absolute_deaths.two_doses,
+            population: self.person_weeks.two_doses,
+            reference_events: self.absolute_deaths.unvaccinated,
+            reference_population: self.person_weeks.unvaccinated,
+        }
+    }
+
+    /// Deaths of people with a booster compared against the unvaccinated.
+    pub fn deaths_of_three_doses(&self) -> Comparison {
+        Comparison {
+            events: self.absolute_deaths.three_doses,
+            population: self.person_weeks.three_doses,
+            reference_events: self.absolute_deaths.unvaccinated,
+            reference_population: self.person_weeks.unvaccinated,
+        }
+    }
+
+    /// Infections of people with a single dose compared against the unvaccinated.
+    pub fn cases_of_one_dose(&self) -> Comparison {
+        Comparison {
+            events: self.absolute_cases.one_dose,
+            population: self.person_weeks.one_dose,
+            reference_events: self.absolute_cases.unvaccinated,
+            reference_population: self.person_weeks.unvaccinated,
+        }
+    }
+
+    /// Infections of people with two doses compared against the unvaccinated.
+    pub fn cases_of_two_doses(&self) -> Comparison {
+        Comparison {
+            events: self.absolute_cases.two_doses,
+            population: self.person_weeks.two_doses,
+            reference_events: self.absolute_cases.unvaccinated,
+            reference_population: self.person_weeks.unvaccinated,
+        }
+    }
+
+    /// Infections of people with a booster compared against the unvaccinated.
+    pub fn cases_of_three_doses(&self) -> Comparison {
+        Comparison {
+            events: self.absolute_cases.three_doses,
+            population: self.person_weeks.three_doses,
+            reference_events: self.absolute_cases.unvaccinated,
+            reference_population: self.person_weeks.unvaccinated,
+        }
+    }
+
+    /// Risk of death of people with a single dose relative to the unvaccinated.
+    pub fn risk_ratio_of_one_dose(&self) -> Estimate {
+        self.deaths_of_one_dose().rate_ratio()
+    }
+
+    /// Risk of death of people with two doses relative to the unvaccinated.
+    pub fn risk_ratio_of_two_doses(&self) -> Estimate {
+        self.deaths_of_two_doses().rate_ratio()
+    }
+
+    /// Risk of death of people with a booster relative to the unvaccinated.
+    pub fn risk_ratio_of_three_doses(&self) -> Estimate {
+        self.deaths_of_three_doses().rate_ratio()
+    }
+
+    /// Risk of infection of people with a single dose relative to the unvaccinated.
+    pub fn case_risk_ratio_of_one_dose(&self) -> Estimate {
+        self.cases_of_one_dose().rate_ratio()
+    }
+
+    /// Risk of infection of people with two doses relative to the unvaccinated.
+    pub fn case_risk_ratio_of_two_doses(&self) -> Estimate {
+        self.cases_of_two_doses().rate_ratio()
+    }
+
+    /// Risk of infection of people with a booster relative to the unvaccinated.
+    pub fn case_risk_ratio_of_three_doses(&self) -> Estimate {
+        self.cases_of_three_doses().rate_ratio()
+    }
+
+    /// Effectiveness of a single dose against death.
+    pub fn effectiveness_of_one_dose(&self) -> Estimate {
+        self.risk_ratio_of_one_dose().effectiveness()
+    }
+
+    /// Effectiveness of two doses against death.
+    pub fn effectiveness_of_two_doses(&self) -> Estimate {
+        self.risk_ratio_of_two_doses().effectiveness()
+    }
+
+    /// Effectiveness of a booster against death.
+    pub fn effectiveness_of_three_doses(&self) -> Estimate {
+        self.risk_ratio_of_three_doses().effectiveness()
+    }
+
+    /// Effectiveness of a single dose against infection.
+    pub fn case_effectiveness_of_one_dose(&self) -> Estimate {
+        self.case_risk_ratio_of_one_dose().effectiveness()
+    }
+
+    /// Effectiveness of two doses against infection.
+    pub fn case_effectiveness_of_two_doses(&self) -> Estimate {
+        self.case_risk_ratio_of_two_doses().effectiveness()
+    }
+
+    /// Effectiveness of a booster against infection.
+    pub fn case_effectiveness_of_three_doses(&self) -> Estimate {
+        self.case_risk_ratio_of_three_doses().effectiveness()
+    }
+
+    /// Share of infected unvaccinated people who died.
+    pub fn cfr_unvaccinated(&self) -> f64 {
+        self.absolute_deaths.unvaccinated as f64 / self.absolute_cases.unvaccinated as f64
+    }
+
+    /// Share of infected people with a single dose who died.
+    pub fn cfr_one_dose(&self) -> f64 {
+        self.absolute_deaths.one_dose as f64 / self.absolute_cases.one_dose as f64
+    }
+
+    /// Share of infected people with two doses who died.
+    pub fn cfr_two_doses(&self) -> f64 {
+        self.absolute_deaths.two_doses as f64 / self.absolute_cases.two_doses as f64
+    }
+
+    /// Share of infected people with a booster who died.
+    pub fn cfr_three_doses(&self) -> f64 {
+        self.absolute_deaths.three_doses as f64 / self.absolute_cases.three_doses as f64
+    }
+}
+
+/// Age groups of the ECDC vaccination data. Deaths and demographics are grouped by year,
+/// so they can be regrouped into these.
+pub const AGE_GROUPS: &[AgeGroup] = &[
+    AgeGroup::new(0, 4),
+    AgeGroup::new(5, 9),
+    AgeGroup::new(10, 14),
+    AgeGroup::new(15, 17),
+    AgeGroup::new(18, 24),
+    AgeGroup::new(25, 49),
+    AgeGroup::new(50, 59),
+    AgeGroup::new(60, 69),
+    AgeGroup::new(70, 79),
+    AgeGroup::open_ended(80),
+];
+
+/// Reports of every week and age group of a stratum. This type should contain every
+/// source and calculated data needed for presentation.
+pub struct WeeklyReports {
+    pub(crate) stratum: Stratum,
+    pub(crate) age_groups: Vec<AgeGroup>,
+    pub(crate) standard_population: StandardPopulation,
+    /// Weights of age groups in the standard population.
+    pub(crate) standard_weights: HashMap<AgeGroup, f64>,
+    pub(crate) weeks: Vec<(YearWeek, HashMap<AgeGroup, WeeklyReport>)>,
+}
+
+impl WeeklyReports {
+    /// Builder of reports out of data in memory.
+    pub fn builder() -> WeeklyReportsBuilder {
+        WeeklyReportsBuilder::default()
+    }
+
+    /// Stratum the reports are calculated for.
+    pub fn stratum(&self) -> Stratum {
+        self.stratum
+    }
+
+    /// Age groups every week is reported for, in order.
+    pub fn age_groups(&self) -> &[AgeGroup] {
+        &self.age_groups
+    }
+
+    /// Population age-standardized rates are weighted with.
+    pub fn standard_population(&self) -> StandardPopulation {
+        self.standard_population
+    }
+
+    /// Reports of every age group, week by week.
+    pub fn weeks(&self) -> &[(YearWeek, HashMap<AgeGroup, WeeklyReport>)] {
+        &self.weeks
+    }
+
+    /// Report of a given week and age group.
+    pub fn get(&self, week: YearWeek, age_group: AgeGroup) -> Option<&WeeklyReport> {
+        let index = self
+            .weeks
+            .binary_search_by_key(&week, |(week, _)| *week)
+            .ok()?;
+        self.weeks[index].1.get(&age_group)
+    }
+
+    /// Sum of a count over age groups.
+    pub fn total(&self, count: impl Fn(&WeeklyReport) -> f64) -> Vec<(YearWeek, f64)> {
+        self.weeks
+            .iter()
+            .map(|(week, reports)| (*week, reports.values().map(&count).sum()))
+            .collect()
+    }
+
+    /// Ratio of two counts summed over age groups, e.g. deaths over cases, so that each age
+    /// group weighs as much as its denominator.
+    pub fn pooled_ratio(
+        &self,
+        numerator: impl Fn(&WeeklyReport) -> f64,
+        denominator: impl Fn(&WeeklyReport) -> f64,
+    ) -> Vec<(YearWeek, f64)> {
+        self.total(numerator)
+            .into_iter()
+            .zip(self.total(denominator))
+            .map(|((week, numerator), (_, denominator))| (week, numerator / denominator))
+            .collect()
+    }
+
+    /// Age groups of a week where every vaccination status anybody has that week has
+    /// people, so that rates of every status can be standardized over the same age mix.
+    fn comparable_age_groups<'a>(
+        &self,
+        reports: &'a HashMap<AgeGroup, WeeklyReport>,
+    ) -> Vec<(&'a AgeGroup, &'a WeeklyReport)> {
+        let people = |report: &WeeklyReport| {
+            [
+                report.unvaccinated_people,
+                report.vaccinated_people.one_dose,
+                report.vaccinated_people.two_doses,
+                report.vaccinated_people.three_doses,
+            ]
+        };
+        let present: Vec<usize> = (0..4)
+            .filter(|&status| reports.values().any(|report| people(report)[status] > 0))
+            .collect();
+
+        reports
+            .iter()
+            .filter(|(_, report)| present.iter().all(|&status| people(report)[status] > 0))
+            .collect()
+    }
+
+    /// Share of the standard population left out of age-standardized rates of every week,
+    /// as its age groups lack people of some vaccination status.
+    pub fn excluded_weights(&self) -> Vec<(YearWeek, f64)> {
+        self.weeks
+            .iter()
+            .map(|(week, reports)| {
+                let included: f64 = self
+                    .comparable_age_groups(reports)
+                    .iter()
+                    .map(|(age_group, _)| self.standard_weights[age_group])
+                    .sum();
+                (*week, (1.0 - included).max(0.0))
+            })
+            .collect()
+    }
+
+    /// Rate directly standardized for age, i.e. weighted with the standard population.
+    /// Every vaccination status is standardized over the same age groups, those where
+    /// each of them has people; weights of the remaining ones are rescaled and the share
+    /// left out is given by `excluded_weights`. The rate is `NaN` if no age group is left,
+    /// or if it's undefined in any of them.
+    pub fn standardized(&self, rate: impl Fn(&WeeklyReport) -> f64) -> Vec<(YearWeek, f64)> {
+        self.weeks
+            .iter()
+            .map(|(week, reports)| {
+                let (weighted, weights) = self
+                    .comparable_age_groups(reports)
+                    .into_iter()
+                    .map(|(age_group, report)| (rate(report), self.standard_weights[age_group]))
+                    .fold((0.0, 0.0), |(weighted, weights), (rate, weight)| {
+                        (weighted + rate * weight, weights + weight)
+                    });
+                (*week, weighted / weights)
+            })
+            .collect()
+    }
+
+    /// Mantel-Haenszel rate ratio pooled over age groups.
+    pub fn pooled(
+        &self,
+        comparison: impl Fn(&WeeklyReport) -> Comparison,
+    ) -> Vec<(YearWeek, Estimate)> {
+        self.weeks
+            .iter()
+            .map(|(week, report)| (*week, mantel_haenszel(report.values().map(&comparison))))
+            .collect()
+    }
+
+    /// Mantel-Haenszel rate ratio pooled over age groups of several strata, e.g. of both
+    /// sexes, so that it's adjusted for them besides age. Weeks are those of the first
+    /// stratum.
+    pub fn pooled_over(
+        strata: &[WeeklyReports],
+        comparison: impl Fn(&WeeklyReport) -> Comparison,
+    ) -> Vec<(YearWeek, Estimate)> {
+        let weeks = match strata.first() {
+            Some(reports) => reports.weeks.iter().map(|(week, _)| *week),
+            None => return Vec::new(),
+        };
+        weeks
+            .map(|week| {
+                let comparisons = strata
+                    .iter()
+                    .filter_map(|reports| reports.weeks.iter().find(|(other, _)| *other == week))
+                    .flat_map(|(_, report)| report.values().map(&comparison));
+                (week, mantel_haenszel(comparisons))
+            })
+            .collect()
+    }
+}
+
+/// Builds `WeeklyReports` out of reports already in memory, e.g. computed from other
+/// sources than BASiW, ECDC and GUS files. By default the reports are of the whole
+/// population, standardized with the European Standard Population.
+#[derive(Default)]
+pub struct WeeklyReportsBuilder {
+    stratum: Stratum,
+    standard_weights: Option<(StandardPopulation, HashMap<AgeGroup, f64>)>,
+    age_groups: Vec<AgeGroup>,
+    weeks: BTreeMap<YearWeek, HashMap<AgeGroup, WeeklyReport>>,
+}
+
+impl WeeklyReportsBuilder {
+    /// Stratum the reports are calculated for.
+    pub fn stratum(mut self, stratum: Stratum) -> Self {
+        self.stratum = stratum;
+        self
+    }
+
+    /// Weights of age groups in a standard population, e.g. from
+    /// `StandardPopulation::weights`.
+    pub fn standard_weights(
+        mut self,
+        standard_population: StandardPopulation,
+        weights: HashMap<AgeGroup, f64>,
+    ) -> Self {
+        self.standard_weights = Some((standard_population, weights));
+        self
+    }
+
+    /// Adds the report of a week and age group, replacing any previous one. Age groups are
+    /// kept in order they are first added in.
+    pub fn report(mut self, week: YearWeek, age_group: AgeGroup, report: WeeklyReport) -> Self {
+        if !self.age_groups.contains(&age_group) {
+            self.age_groups.push(age_group);
+        }
+        self.weeks
+            .entry(week)
+            .or_default()
+            .insert(age_group, report);
+        self
+    }
+
+    /// Checks that every week has a report of every age group, and that every age group
+    /// has a standard weight.
+    pub fn build(self) -> anyhow::Result<WeeklyReports> {
+        let (standard_population, standard_weights) = match self.standard_weights {
+            Some(weights) => weights,
+            None => (
+                StandardPopulation::European,
+                StandardPopulation::European.weights(&AgeDistribution::default(), &self.age_groups),
+            ),
+        };
+
+        for age_group in &self.age_groups {
+            if !standard_weights.contains_key(age_group) {
+                bail!("age group {} has no standard weight", age_group);
+            }
+            for (week, reports) in &self.weeks {
+                if !reports.contains_key(age_group) {
+                    bail!("week {} has no report of age group {}", week, age_group);
+                }
+            }
+        }
+
+        Ok(WeeklyReports {
+            stratum: self.stratum,
+            age_groups: self.age_groups,
+            standard_population,
+            standard_weights,
+            weeks: self.weeks.into_iter().collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn week_of(year: i32, month: u32, day: u32) -> YearWeek {
+        YearWeek::from(
+            NaiveDate::from_ymd_opt(year, month, day)
+                .unwrap()
+                .iso_week(),
+        )
+    }
+
+    #[test]
+    fn first_days_of_a_year_may_belong_to_the_last_week_of_the_previous_one() {
+        assert_eq!(week_of(2021, 1, 1), YearWeek((2020, 53)));
+        assert_eq!(week_of(2021, 1, 3), YearWeek((2020, 53)));
+        assert_eq!(week_of(2021, 1, 4), YearWeek((2021, 1)));
+        assert_eq!(week_of(2022, 1, 2), YearWeek((2021, 52)));
+        assert_eq!(week_of(2022, 1, 3), YearWeek((2022, 1)));
+    }
+
+    #[test]
+    fn last_days_of_a_year_may_belong_to_the_first_week_of_the_next_one() {
+        assert_eq!(week_of(2024, 12, 29), YearWeek((2024, 52)));
+        assert_eq!(week_of(2024, 12, 30), YearWeek((2025, 1)));
+    }
+
+    #[test]
+    fn weeks_are_ordered_across_years() {
+        assert!(YearWeek((2020, 53)) < YearWeek((2021, 1)));
+        assert!(YearWeek((2021, 52)) < YearWeek((2022, 1)));
+    }
+
+    #[test]
+    fn weeks_continue_into_the_next_year() {
+        let weeks: Vec<_> = weeks(YearWeek((2021, 51)), YearWeek((2022, 2))).collect();
+        assert_eq!(
+            weeks,
+            [
+                YearWeek((2021, 51)),
+                YearWeek((2021, 52)),
+                YearWeek((2022, 1)),
+                YearWeek((2022, 2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn weeks_start_with_the_week_of_new_year_2021() {
+        let weeks: Vec<_> = weeks(YearWeek((2020, 1)), YearWeek((2021, 1))).collect();
+        assert_eq!(weeks, [YearWeek((2020, 53)), YearWeek((2021, 1))]);
+    }
+
+    #[test]
+    fn weeks_are_counted_across_years() {
+        assert_eq!(
+            YearWeek((2022, 1)).weeks_since(YearWeek((2022, 1))),
+            Some(0)
+        );
+        assert_eq!(
+            YearWeek((2021, 1)).weeks_since(YearWeek((2020, 53))),
+            Some(1)
+        );
+        assert_eq!(
+            YearWeek((2022, 2)).weeks_since(YearWeek((2021, 51))),
+            Some(3)
+        );
+        assert_eq!(YearWeek((2021, 51)).weeks_since(YearWeek((2022, 2))), None);
+        assert_eq!(YearWeek((2021, 53)).weeks_since(YearWeek((2021, 1))), None);
+    }
+
+    #[test]
+    fn earlier_weeks_reach_into_the_previous_year() {
+        assert_eq!(
+            YearWeek((2022, 2)).weeks_before(0),
+            Some(YearWeek((2022, 2)))
+        );
+        assert_eq!(
+            YearWeek((2022, 2)).weeks_before(2),
+            Some(YearWeek((2021, 52)))
+        );
+        assert_eq!(
+            YearWeek((2021, 1)).weeks_before(1),
+            Some(YearWeek((2020, 53)))
+        );
+        assert_eq!(YearWeek((2021, 53)).weeks_before(1), None);
+    }
+
+    #[test]
+    fn every_status_is_standardized_over_the_same_age_groups() {
+        let week = YearWeek((2022, 1));
+        let (young, old) = (AgeGroup::new(20, 29), AgeGroup::open_ended(80));
+        let deaths = DeathRate {
+            unvaccinated: 1,
+            one_dose: 0,
+            two_doses: 1,
+            three_doses: 1,
+        };
+        let report = |three_doses| {
+            let vaccinated_people = VaccinatedPeople {
+                two_doses: 1_000,
+                three_doses,
+                ..VaccinatedPeople::default()
+            };
+            WeeklyReport::new(vaccinated_people, 1_000, deaths, deaths)
+        };
+        let weights = HashMap::from([(young, 0.75), (old, 0.25)]);
+        let reports = WeeklyReports::builder()
+            .standard_weights(StandardPopulation::European, weights)
+            .report(week, young, report(0))
+            .report(week, old, report(500))
+            .build()
+            .unwrap();
+
+        // Nobody young got a booster, so the young are left out of every status.
+        let unvaccinated = reports.standardized(|report| report.deaths_per_million.unvaccinated);
+        let boosted = reports.standardized(|report| report.deaths_per_million.three_doses);
+        assert_eq!(unvaccinated, [(week, 1_000.0)]);
+        assert_eq!(boosted, [(week, 2_000.0)]);
+        assert_eq!(reports.excluded_weights(), [(week, 0.75)]);
+    }
+
+    #[test]
+    fn rate_ratios_are_pooled_over_strata() {
+        let week = YearWeek((2022, 1));
+        let age_group = AgeGroup::open_ended(80);
+        let report = |unvaccinated, two_doses| {
+            let vaccinated_people = VaccinatedPeople {
+                two_doses: 1_000,
+                ..VaccinatedPeople::default()
+            };
+            let deaths = DeathRate {
+                unvaccinated,
+                one_dose: 0,
+                two_doses,
+                three_doses: 0,
+            };
+            WeeklyReport::new(vaccinated_people, 1_000, deaths, deaths)
+        };
+        let stratum = |sex, unvaccinated, two_doses| {
+            WeeklyReports::builder()
+                .stratum(Stratum {
+                    sex: Some(sex),
+                    ..Stratum::default()
+                })
+                .report(week, age_group, report(unvaccinated, two_doses))
+                .build()
+                .unwrap()
+        };
+
+        // Both sexes halve the risk, one of them with five times as many deaths.
+        let strata = [stratum(Sex::Male, 10, 5), stratum(Sex::Female, 2, 1)];
+        let pooled = WeeklyReports::pooled_over(&strata, WeeklyReport::deaths_of_two_doses);
+        assert_eq!(pooled.len(), 1);
+        assert_eq!(pooled[0].0, week);
+        assert!((pooled[0].1.value - 0.5).abs() < 1e-12);
+        assert!(WeeklyReports::pooled_over(&[], WeeklyReport::deaths_of_two_doses).is_empty());
+    }
+
+    #[test]
+    fn weeks_are_parsed_in_both_notations() {
+        assert_eq!("2020W53".parse::<YearWeek>().unwrap(), YearWeek((2020, 53)));
+        assert_eq!("2022-W01".parse::<YearWeek>().unwrap(), YearWeek((2022, 1)));
+        assert_eq!(YearWeek((2022, 1)).to_string(), "2022W01");
+        assert!("2022".parse::<YearWeek>().is_err());
+        assert!("2022-Wxx".parse::<YearWeek>().is_err());
+    }
+}
@@ -1,266 +1,329 @@
-use std::{collections::HashMap, fmt::Display, hash::Hash, ops::Add};
-
-use chrono::{Datelike, IsoWeek, NaiveDate};
-use demographics::age_distribution;
-use statrs::statistics::Statistics;
-use vaccination::VaccinatedPeople;
-
-use crate::{
-    deaths::{DeathsData, InfectionsData, VaccinationStatus},
-    vaccination::VaccinationData,
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use clap::Parser;
+use covid_vaccine_efficacy::{
+    datasets::Datasets,
+    export::{self, MetricName},
+    plots, sensitivity,
+    snapshots::SnapshotFiles,
+    tables, waning, WeeklyReports, YearWeek,
 };
 
-mod deaths;
-mod demographics;
-mod plots;
-mod tables;
-mod vaccination;
+use crate::cli::{Cli, Command, Options};
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
-struct YearWeek((u32, u32));
+mod cli;
 
-impl From<IsoWeek> for YearWeek {
-    fn from(week: IsoWeek) -> Self {
-        YearWeek((week.year() as u32, week.week() as u32))
-    }
-}
+fn print_tables(reports: &WeeklyReports, output: &Path, print: bool) -> anyhow::Result<()> {
+    let stratum = reports.stratum();
+    for age_group in reports.age_groups() {
+        let weekly_reports = reports
+            .weeks()
+            .iter()
+            .map(|(week, report)| (*week, report[age_group]));
+        let table = if stratum.has_denominator() {
+            tables::stats_for_age_group(weekly_reports)
+        } else {
+            tables::deaths_for_age_group(weekly_reports)
+        };
+
+        if print {
+            println!("Grupa wiekowa {} ({})", age_group, stratum);
+            if !stratum.has_denominator() {
+                println!("Tylko zgony - brak danych o liczebności tej grupy.");
+            }
+            table.print_tty(false)?;
+            println!();
+        }
 
-impl Display for YearWeek {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}W{:02}", self.0 .0, self.0 .1)
+        tables::write_csv(&table, *age_group, output)?;
     }
-}
 
-fn weeks(max: YearWeek) -> impl Iterator<Item = YearWeek> {
-    NaiveDate::from_ymd(2021, 1, 1)
-        .iter_weeks()
-        .map(|week| YearWeek::from(week.iso_week()))
-        .take_while(move |week| week <= &max)
+    Ok(())
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-struct AgeGroup((usize, usize));
-
-impl AgeGroup {
-    const fn new(from: usize, to: usize) -> Self {
-        Self((from, to))
+/// Prints rate ratios by time since the last dose and writes them as `waning.csv`.
+fn print_waning(
+    datasets: &Datasets,
+    reports: &WeeklyReports,
+    output: &Path,
+    print: bool,
+) -> anyhow::Result<()> {
+    if !reports.stratum().has_denominator() {
+        return Ok(());
     }
+    let table = tables::waning(&waning::by_time_since_dose(datasets, reports));
 
-    fn includes(&self, age: usize) -> bool {
-        age >= self.0 .0 && age <= self.0 .1
+    if print {
+        println!(
+            "Ryzyko względne wg czasu od ostatniej dawki ({})",
+            reports.stratum()
+        );
+        table.print_tty(false)?;
+        println!();
     }
-}
 
-impl Display for AgeGroup {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} - {}", self.0 .0, self.0 .1)
-    }
+    tables::write_summary_csv(&table, "waning", output)
 }
 
-fn per_million(nominator: usize, denominator: usize) -> f64 {
-    nominator as f64 * 1_000_000_f64 / denominator as f64
-}
+/// Prints rate ratios pooled over weeks and age groups with every protection lag and
+/// writes them as `protection_lags.csv`.
+fn print_protection_lags(
+    datasets: &Datasets,
+    weeks: &[YearWeek],
+    reports: &WeeklyReports,
+    output: &Path,
+    print: bool,
+) -> anyhow::Result<()> {
+    if !reports.stratum().has_denominator() {
+        return Ok(());
+    }
+    let table = tables::protection_lags(&sensitivity::protection_lags(
+        datasets,
+        weeks,
+        reports.age_groups(),
+        reports.stratum(),
+    )?);
+
+    if print {
+        println!(
+            "Ryzyko względne wg opóźnienia ochrony po dawce ({})",
+            reports.stratum()
+        );
+        table.print_tty(false)?;
+        println!();
+    }
 
-#[derive(Debug, Clone, Copy)]
-struct DeathRate<T> {
-    unvaccinated: T,
-    two_doses: T,
-    three_doses: T,
+    tables::write_summary_csv(&table, "protection_lags", output)
 }
 
-impl<T: Add<Output = T> + Copy> DeathRate<T> {
-    fn total(&self) -> T {
-        self.unvaccinated + self.two_doses + self.three_doses
+/// Prints rate ratios pooled over age groups of both sexes and writes them as
+/// `sex_adjusted.csv`.
+fn print_sex_adjusted(
+    reports: &WeeklyReports,
+    by_sex: &[WeeklyReports],
+    output: &Path,
+    print: bool,
+) -> anyhow::Result<()> {
+    let table = tables::pooled_rate_ratios(by_sex);
+
+    if print {
+        println!(
+            "Ryzyko względne skorygowane o wiek i płeć ({})",
+            reports.stratum()
+        );
+        table.print_tty(false)?;
+        println!();
     }
-}
 
-#[derive(Debug, Clone, Copy)]
-struct WeeklyReport {
-    vaccinated_people: VaccinatedPeople,
-    unvaccinated_people: usize,
-    absolute_cases: DeathRate<usize>,
-    absolute_deaths: DeathRate<usize>,
-    deaths_per_million: DeathRate<f64>,
-    cases_per_million: DeathRate<f64>,
+    tables::write_summary_csv(&table, "sex_adjusted", output)
 }
 
-impl WeeklyReport {
-    fn risk_ratio_of_two_doses(&self) -> f64 {
-        self.deaths_per_million.two_doses / self.deaths_per_million.unvaccinated
+fn draw_plots(
+    reports: &WeeklyReports,
+    heatmaps: &[MetricName],
+    output: &Path,
+) -> anyhow::Result<()> {
+    plots::draw_deaths(reports, output)?;
+    if !reports.stratum().has_denominator() {
+        return Ok(());
     }
 
-    fn risk_ratio_of_three_doses(&self) -> f64 {
-        self.deaths_per_million.three_doses / self.deaths_per_million.unvaccinated
-    }
+    plots::draw_deaths_per_million_per_vaccination_status(reports, output)?;
+    plots::draw_risk_ratios(reports, output)?;
+    plots::draw_case_risk_ratios(reports, output)?;
+    plots::draw_cfr(reports, output)?;
+    plots::draw_effectiveness(reports, output)?;
 
-    fn case_risk_ratio_of_two_doses(&self) -> f64 {
-        self.cases_per_million.two_doses / self.cases_per_million.unvaccinated
-    }
-
-    fn case_risk_ratio_of_three_doses(&self) -> f64 {
-        self.cases_per_million.three_doses / self.cases_per_million.unvaccinated
-    }
+    plots::draw_risk_ratios_by_age_group(reports, output)?;
+    plots::draw_case_risk_ratios_by_age_group(reports, output)?;
+    plots::draw_cfr_by_age_group(reports, output)?;
+    plots::draw_deaths_per_million_by_age_group(reports, output)?;
 
-    fn cfr_unvaccinated(&self) -> f64 {
-        self.absolute_deaths.unvaccinated as f64 / self.absolute_cases.unvaccinated as f64
-    }
+    plots::draw_vaccinations_one_dose(reports, output)?;
+    plots::draw_vaccinations_two_doses(reports, output)?;
+    plots::draw_vaccinations_at_least_two_doses(reports, output)?;
 
-    fn cfr_two_doses(&self) -> f64 {
-        self.absolute_deaths.two_doses as f64 / self.absolute_cases.two_doses as f64
+    for heatmap in heatmaps {
+        plots::draw_heatmap(reports, *heatmap, output)?;
     }
 
-    fn cfr_three_doses(&self) -> f64 {
-        self.absolute_deaths.three_doses as f64 / self.absolute_cases.three_doses as f64
-    }
+    Ok(())
 }
 
-// Deaths and demographics are grouped by year, ECDC vaccination data however is
-// reported as age groups.
-const AGE_GROUPS: &'static [AgeGroup] = &[
-    AgeGroup::new(0, 4),
-    AgeGroup::new(5, 9),
-    AgeGroup::new(10, 14),
-    AgeGroup::new(15, 17),
-    AgeGroup::new(18, 24),
-    AgeGroup::new(25, 49),
-    AgeGroup::new(50, 59),
-    AgeGroup::new(60, 69),
-    AgeGroup::new(70, 79),
-    // FIXME: Population is miscalculated for this group. Thank you GUS.
-    //AgeGroup::new(80, 120),
-];
-
-/// This type should contain every source and calculated data needed for presentation.
-struct WeeklyReports(Vec<(YearWeek, HashMap<AgeGroup, WeeklyReport>)>);
-
-impl WeeklyReports {
-    fn mean(&self, metric: impl Fn(&WeeklyReport) -> f64) -> Vec<(YearWeek, f64)> {
-        self.0
-            .iter()
-            .map(|(week, report)| {
-                (
-                    *week,
-                    report
-                        .values()
-                        .map(&metric)
-                        .filter(|rr| rr.is_finite())
-                        .mean(),
-                )
-            })
-            .collect()
-    }
+/// What a command writes for every stratum.
+#[derive(Debug, Clone, Copy)]
+struct Outputs {
+    /// Tables written as CSV files.
+    tables: bool,
+    /// Whether the tables are printed as well.
+    print: bool,
+    /// Plots.
+    plots: bool,
+    /// Reports in long format.
+    export: bool,
 }
 
-fn main() -> anyhow::Result<()> {
-    println!("Ładowanie danych o demografii.");
-    let ages = age_distribution();
-    let total_population = ages.population();
-
-    println!("Ładowanie danych o szczepieniach.");
-    let vaccinations = VaccinationData::new();
-
-    println!("Ładowanie danych o zgonach.");
-    let deaths = DeathsData::new();
-
-    println!("Ładowanie danych o infekcjach.");
-    let cases = InfectionsData::new();
-
-    println!("Populacja ogólna: {}", total_population);
-    println!("Zgonów COVID-19: {}", deaths.total_deaths);
-
-    let weekly_report = |week: YearWeek, age_group: AgeGroup| -> WeeklyReport {
-        let population = ages.population_of(age_group);
-        let vaccinated_people = vaccinations.sum(age_group.0, week);
-        let unvaccinated_people = population - vaccinated_people.at_least_one_dose;
-
-        let absolute_deaths = DeathRate {
-            unvaccinated: deaths.by_vaccination_status(
-                week,
-                age_group,
-                VaccinationStatus::Unvaccinated,
-            ),
-            two_doses: deaths.by_vaccination_status(week, age_group, VaccinationStatus::TwoDoses),
-            three_doses: deaths.by_vaccination_status(
-                week,
-                age_group,
-                VaccinationStatus::ThreeDoses,
-            ),
-        };
-
-        let absolute_cases = DeathRate {
-            unvaccinated: cases.by_vaccination_status(
-                week,
-                age_group,
-                VaccinationStatus::Unvaccinated,
-            ),
-            two_doses: cases.by_vaccination_status(week, age_group, VaccinationStatus::TwoDoses),
-            three_doses: cases.by_vaccination_status(
-                week,
-                age_group,
-                VaccinationStatus::ThreeDoses,
-            ),
-        };
-
-        let deaths_per_million = DeathRate {
-            unvaccinated: per_million(absolute_deaths.unvaccinated, unvaccinated_people),
-            two_doses: per_million(absolute_deaths.two_doses, vaccinated_people.two_doses),
-            three_doses: per_million(absolute_deaths.three_doses, vaccinated_people.three_doses),
-        };
+/// Runs the command against a single BASiW snapshot.
+fn run(command: &Command, files: &SnapshotFiles) -> anyhow::Result<()> {
+    let options = command.options();
+    let output = options.output_of(files.snapshot);
 
-        let cases_per_million = DeathRate {
-            unvaccinated: per_million(absolute_cases.unvaccinated, unvaccinated_people),
-            two_doses: per_million(absolute_cases.two_doses, vaccinated_people.two_doses),
-            three_doses: per_million(absolute_cases.three_doses, vaccinated_people.three_doses),
-        };
-
-        WeeklyReport {
-            vaccinated_people,
-            unvaccinated_people,
-            absolute_cases,
-            absolute_deaths,
-            cases_per_million,
-            deaths_per_million,
+    println!("Ładowanie danych o szczepieniach i demografii.");
+    println!(
+        "Ładowanie danych o zgonach ({}) i infekcjach ({}).",
+        files.deaths.display(),
+        files.cases.display()
+    );
+    let mut datasets = Datasets::load(&options.vaccinations(), &options.population(), files)?;
+    datasets.protection_lag = options.protection_lag;
+    let weeks = datasets.weeks(options.from, options.to);
+
+    println!("Populacja ogólna: {}", datasets.ages.population());
+    println!("Zgonów COVID-19: {}", datasets.deaths.total_deaths);
+
+    let clamped_cells: Vec<String> = options
+        .strata()
+        .iter()
+        .flat_map(|stratum| datasets.clamped_cells(&weeks, &options.age_groups(), stratum))
+        .collect();
+
+    let outputs = match command {
+        Command::Validate(_) => {
+            return validate(&datasets, options, &weeks, clamped_cells, &output);
         }
+        Command::Report(_) => Outputs {
+            tables: true,
+            print: true,
+            plots: true,
+            export: false,
+        },
+        Command::Tables(_) => Outputs {
+            tables: true,
+            print: true,
+            plots: false,
+            export: false,
+        },
+        Command::Plots(_) => Outputs {
+            tables: false,
+            print: false,
+            plots: true,
+            export: false,
+        },
+        Command::Export(_) => Outputs {
+            tables: true,
+            print: false,
+            plots: false,
+            export: true,
+        },
     };
+    report_strata(&datasets, options, &weeks, outputs, &output)?;
+    report_quality(&datasets, clamped_cells, &output)
+}
 
-    let max_week = *[cases.max_week(), deaths.max_week()].iter().min().unwrap();
+/// Writes outputs of every stratum to a directory of its own.
+fn report_strata(
+    datasets: &Datasets,
+    options: &Options,
+    weeks: &[YearWeek],
+    outputs: Outputs,
+    output: &Path,
+) -> anyhow::Result<()> {
+    for stratum in options.strata() {
+        let output = match stratum.slug() {
+            Some(slug) => output.join(slug),
+            None => output.to_owned(),
+        };
+        std::fs::create_dir_all(&output)
+            .with_context(|| format!("can't create {}", output.display()))?;
+
+        let reports = datasets.weekly_reports(
+            weeks,
+            &options.age_groups(),
+            stratum,
+            options.standard_population,
+        )?;
+        let by_sex = options
+            .sexes_of(&stratum)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|stratum| {
+                datasets.weekly_reports(
+                    weeks,
+                    &options.age_groups(),
+                    stratum,
+                    options.standard_population,
+                )
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        if outputs.tables {
+            print_tables(&reports, &output, outputs.print)?;
+            print_waning(datasets, &reports, &output, outputs.print)?;
+            print_protection_lags(datasets, weeks, &reports, &output, outputs.print)?;
+            if !by_sex.is_empty() {
+                print_sex_adjusted(&reports, &by_sex, &output, outputs.print)?;
+            }
+        }
+        if outputs.plots {
+            draw_plots(&reports, options.heatmaps(), &output)?;
+            if !by_sex.is_empty() {
+                plots::draw_sex_adjusted_risk_ratios(&reports, &by_sex, &output)?;
+            }
+        }
+        if outputs.export {
+            for format in options.formats() {
+                export::write(&reports, *format, &output)?;
+            }
+        }
+    }
+    Ok(())
+}
 
-    for age_group in AGE_GROUPS {
-        let weekly_reports = weeks(max_week).map(|week| (week, weekly_report(week, *age_group)));
-        println!(
-            "Grupa wiekowa {} (populacja: {})",
-            age_group,
-            ages.population_of(*age_group)
-        );
-        tables::print_stats_for_age_group(*age_group, weekly_reports);
-        println!("");
+/// Checks the datasets against each other instead of reporting on them, and fails if
+/// they're inconsistent.
+fn validate(
+    datasets: &Datasets,
+    options: &Options,
+    weeks: &[YearWeek],
+    clamped_cells: Vec<String>,
+    output: &Path,
+) -> anyhow::Result<()> {
+    report_quality(datasets, clamped_cells, output)?;
+    let problems = datasets.validate(weeks, &options.age_groups(), &options.strata());
+    for problem in &problems {
+        println!("{}", problem);
     }
+    if !problems.is_empty() {
+        return Err(anyhow!("znaleziono {} problemów w danych", problems.len()));
+    }
+    println!("Dane są spójne.");
+    Ok(())
+}
 
-    let weekly_reports_per_age_group = WeeklyReports(
-        weeks(max_week)
-            .map(|week| {
-                (
-                    week,
-                    HashMap::<AgeGroup, WeeklyReport>::from_iter(
-                        AGE_GROUPS
-                            .iter()
-                            .map(|age_group| (*age_group, weekly_report(week, *age_group))),
-                    ),
-                )
-            })
-            .collect(),
-    );
+/// Prints rows dropped from source files, and cells whose unvaccinated people were clamped
+/// at zero, and writes them to `data_quality.json`.
+fn report_quality(
+    datasets: &Datasets,
+    clamped_cells: Vec<String>,
+    output: &Path,
+) -> anyhow::Result<()> {
+    let quality = datasets.quality(clamped_cells);
+    println!("{}", quality);
+    std::fs::create_dir_all(output)
+        .with_context(|| format!("can't create {}", output.display()))?;
+    quality.write(output)
+}
 
-    plots::draw_deaths(&weekly_reports_per_age_group);
-    plots::draw_deaths_per_million_per_vaccination_status(&weekly_reports_per_age_group);
-    plots::draw_weekly_vaccinations(&vaccinations)?;
-    plots::draw_risk_ratios(&weekly_reports_per_age_group);
-    plots::draw_case_risk_ratios(&weekly_reports_per_age_group);
-    plots::draw_cfr(&weekly_reports_per_age_group);
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
 
-    plots::draw_vaccinations_one_dose(&weekly_reports_per_age_group);
-    plots::draw_vaccinations_two_doses(&weekly_reports_per_age_group);
-    plots::draw_vaccinations_at_least_two_doses(&weekly_reports_per_age_group);
+    for files in cli.command.options().snapshot_files()? {
+        if let Some(snapshot) = files.snapshot {
+            println!("Migawka BASiW z dnia {}.", snapshot);
+        }
+        run(&cli.command, &files)?;
+    }
 
     Ok(())
 }
@@ -1,457 +1,1151 @@
-use chrono::{Datelike, NaiveDate};
-use plotters::{coord::types::RangedCoordu32, prelude::*};
+//! Plots of weekly reports, written as SVG files.
 
-use crate::{vaccination::VaccinationData, WeeklyReport, WeeklyReports, YearWeek, AGE_GROUPS};
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
 
-pub(crate) fn draw_weekly_vaccinations(people_vaccinated: &VaccinationData) -> anyhow::Result<()> {
-    let path = format!("output/vaccinated_people.png");
-    let area = BitMapBackend::new(path.as_str(), (1024, 400)).into_drawing_area();
+use anyhow::bail;
+use plotters::{
+    coord::{ranged1d::ValueFormatter, types::RangedCoordf64},
+    prelude::*,
+    style::RGBAColor,
+};
 
-    let weeks: Vec<YearWeek> = NaiveDate::from_ymd(2021, 1, 1)
-        .iter_weeks()
-        .take(52)
-        .map(|date| date.iso_week().into())
-        .collect();
+use crate::{
+    deaths::VaccinationStatus,
+    export::{MetricName, Scale},
+    intervals::{Comparison, Estimate},
+    AgeGroup, WeeklyReport, WeeklyReports,
+};
 
-    let x_axis = 0usize..(weeks.len() - 1);
-    let y_axis = 0usize..10_000_000usize;
+/// Value drawn for every week.
+#[derive(Clone, Copy)]
+pub enum Metric {
+    /// Count of a report, e.g. deaths. Age groups are combined with their sum.
+    Value(fn(&WeeklyReport) -> f64),
+    /// Ratio of two counts of a report, e.g. deaths over cases. Age groups are combined
+    /// with the ratio of their sums.
+    Ratio(fn(&WeeklyReport) -> f64, fn(&WeeklyReport) -> f64),
+    /// Rate of a report, e.g. deaths per million. Age groups are combined with an
+    /// age-standardized rate.
+    Rate(fn(&WeeklyReport) -> f64),
+    /// Rate ratio of a comparison against the unvaccinated. Age groups are combined with
+    /// Mantel-Haenszel.
+    RateRatio(fn(&WeeklyReport) -> Comparison),
+    /// Estimate of a report, e.g. effectiveness. Age groups can't be combined.
+    Estimate(fn(&WeeklyReport) -> Estimate),
+}
 
-    let caption = format!("Ilość zaszczepionych osób (50-59) przynajmniej 2 dawkami");
+/// Value of a week without an interval.
+fn exact(value: f64) -> Estimate {
+    Estimate {
+        value,
+        lower: value,
+        upper: value,
+    }
+}
 
-    area.fill(&WHITE)?;
+impl Metric {
+    /// Values of a single age group, week by week.
+    fn of_age_group(self, reports: &WeeklyReports, age_group: &AgeGroup) -> Vec<Estimate> {
+        reports
+            .weeks
+            .iter()
+            .map(|(_, by_age_group)| {
+                let report = &by_age_group[age_group];
+                match self {
+                    Metric::Value(value) | Metric::Rate(value) => exact(value(report)),
+                    Metric::Ratio(numerator, denominator) => {
+                        exact(numerator(report) / denominator(report))
+                    }
+                    Metric::RateRatio(comparison) => comparison(report).rate_ratio(),
+                    Metric::Estimate(estimate) => estimate(report),
+                }
+            })
+            .collect()
+    }
 
-    let mut chart = ChartBuilder::on(&area)
-        .caption(
-            caption.clone(),
-            ("sans-serif", 12).into_font().color(&BLACK),
-        )
-        .set_label_area_size(LabelAreaPosition::Left, 12.percent())
-        .set_label_area_size(LabelAreaPosition::Bottom, 10.percent())
-        .margin(1.percent())
-        .build_cartesian_2d(x_axis.clone(), y_axis.clone())?;
+    /// Values of every age group combined, week by week.
+    fn combined(self, reports: &WeeklyReports) -> anyhow::Result<Vec<Estimate>> {
+        let values = match self {
+            Metric::Value(count) => reports.total(count),
+            Metric::Ratio(numerator, denominator) => reports.pooled_ratio(numerator, denominator),
+            Metric::Rate(rate) => reports.standardized(rate),
+            Metric::RateRatio(comparison) => {
+                return Ok(reports
+                    .pooled(comparison)
+                    .into_iter()
+                    .map(|(_, estimate)| estimate)
+                    .collect())
+            }
+            Metric::Estimate(_) => bail!("Estimates of age groups can't be combined"),
+        };
+        Ok(values.into_iter().map(|(_, value)| exact(value)).collect())
+    }
+}
 
-    chart
-        .configure_mesh()
-        .disable_mesh()
-        .x_desc("Tydzień")
-        .y_desc("Ilość zaszczepionych osób")
-        .x_label_formatter(&|n| format!("{:?}", weeks[*n as usize]))
-        .draw()?;
+/// Lines a chart is split into.
+#[derive(Clone, Copy)]
+pub enum Grouping<'a> {
+    /// A line per age group of the reports.
+    ByAgeGroup(Metric),
+    /// A line per vaccination status, each with its own metric, of every age group combined.
+    ByStatus(&'a [(VaccinationStatus, Metric)]),
+    /// A line per vaccination status, of every age group of several strata combined, e.g.
+    /// adjusted for sex. Only rate ratios can be combined across strata.
+    PooledByStatus(&'a [WeeklyReports], &'a [(VaccinationStatus, Metric)]),
+}
 
-    chart
-        .draw_series(LineSeries::new(
-            weeks
-                .iter()
-                .enumerate()
-                .map(|(n, week)| (n, people_vaccinated.sum((50, 59), *week).at_least_two_doses)),
-            RED.stroke_width(2),
-        ))?
-        .label("dwie dawki")
-        .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 10, y + 5)], RED.filled()));
+/// Look of a chart.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Style {
+    /// Draws values as percentages.
+    pub percent: bool,
+    /// Values below it are drawn at it, e.g. negative effectiveness as zero.
+    pub floor: Option<f64>,
+    /// Shades confidence intervals of estimates and rate ratios.
+    pub intervals: bool,
+    /// Draws the Y axis on a logarithmic scale. Values which aren't positive are left out.
+    pub log_scale: bool,
+    /// Value marked with a horizontal line, e.g. 1 for rate ratios.
+    pub reference: Option<f64>,
+}
 
-    chart
-        .configure_series_labels()
-        .position(SeriesLabelPosition::UpperMiddle)
-        .border_style(&BLACK)
-        .draw()?;
+impl Style {
+    /// Whether a value can be drawn on the Y axis.
+    fn shows(&self, value: f64) -> bool {
+        value.is_finite() && (!self.log_scale || value > 0.0)
+    }
 
-    area.present()?;
+    fn apply(&self, estimate: Estimate) -> Estimate {
+        let estimate = if self.percent {
+            estimate.percent()
+        } else {
+            estimate
+        };
+        let floor = |value: f64| self.floor.map_or(value, |floor| value.max(floor));
+        Estimate {
+            value: floor(estimate.value),
+            lower: floor(estimate.lower),
+            upper: floor(estimate.upper),
+        }
+    }
+}
+
+/// Label of a vaccination status in legends.
+fn status_label(status: VaccinationStatus) -> &'static str {
+    match status {
+        VaccinationStatus::Unvaccinated => "niezaszczepieni",
+        VaccinationStatus::OneDose => "1 dawka",
+        VaccinationStatus::TwoDoses => "2 dawki",
+        VaccinationStatus::ThreeDoses => "3 dawki",
+    }
+}
+
+/// Colour of a vaccination status, the same on every chart.
+fn status_color(status: VaccinationStatus) -> RGBAColor {
+    Palette99::pick(match status {
+        VaccinationStatus::Unvaccinated => 0,
+        VaccinationStatus::TwoDoses => 1,
+        VaccinationStatus::ThreeDoses => 2,
+        VaccinationStatus::OneDose => 3,
+    })
+    .to_rgba()
+}
+
+/// Line of a chart, with a value for every week of the reports.
+struct Line {
+    label: String,
+    color: RGBAColor,
+    values: Vec<Estimate>,
+}
+
+fn lines(reports: &WeeklyReports, grouping: Grouping, style: Style) -> anyhow::Result<Vec<Line>> {
+    let styled = |values: Vec<Estimate>| values.into_iter().map(|value| style.apply(value));
+    match grouping {
+        Grouping::ByAgeGroup(metric) => Ok(reports
+            .age_groups
+            .iter()
+            .enumerate()
+            .map(|(n, age_group)| Line {
+                label: age_group.to_string(),
+                color: Palette99::pick(n).to_rgba(),
+                values: styled(metric.of_age_group(reports, age_group)).collect(),
+            })
+            .collect()),
+        Grouping::ByStatus(statuses) => {
+            status_lines(statuses, style, |metric| metric.combined(reports))
+        }
+        Grouping::PooledByStatus(strata, statuses) => {
+            status_lines(statuses, style, |metric| match metric {
+                Metric::RateRatio(comparison) => Ok(WeeklyReports::pooled_over(strata, comparison)
+                    .into_iter()
+                    .map(|(_, estimate)| estimate)
+                    .collect()),
+                _ => bail!("Only rate ratios can be pooled over strata"),
+            })
+        }
+    }
+}
+
+/// A line per vaccination status, of values the metric of each status gives.
+fn status_lines(
+    statuses: &[(VaccinationStatus, Metric)],
+    style: Style,
+    values: impl Fn(Metric) -> anyhow::Result<Vec<Estimate>>,
+) -> anyhow::Result<Vec<Line>> {
+    statuses
+        .iter()
+        .map(|(status, metric)| {
+            Ok(Line {
+                label: status_label(*status).to_owned(),
+                color: status_color(*status),
+                values: values(*metric)?
+                    .into_iter()
+                    .map(|value| style.apply(value))
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// Range of values of every line and the reference, with some room around. On a linear
+/// scale it starts at zero unless some values are negative. Intervals don't widen it, as
+/// those of small groups can be huge.
+fn value_range<'a>(lines: impl IntoIterator<Item = &'a Line>, style: Style) -> Range<f64> {
+    let values = lines
+        .into_iter()
+        .flat_map(|line| line.values.iter().map(|estimate| estimate.value))
+        .chain(style.reference)
+        .filter(|value| style.shows(*value));
 
+    if style.log_scale {
+        let (min, max) = values.fold((f64::INFINITY, 0f64), |(min, max), value| {
+            (min.min(value), max.max(value))
+        });
+        if max == 0.0 {
+            return 0.1..10.0;
+        }
+        if max <= min {
+            return min / 2.0..max * 2.0;
+        }
+        // The same share of the axis as on a linear scale.
+        let margin = (max / min).powf(0.05);
+        return min / margin..max * margin;
+    }
+
+    let (min, max) = values.fold((0f64, 0f64), |(min, max), value| {
+        (min.min(value), max.max(value))
+    });
+    if max <= min {
+        return min..min + 1.0;
+    }
+    let margin = (max - min) * 0.05;
+    let min = if min < 0.0 { min - margin } else { min };
+    min..max + margin
+}
+
+/// Runs of consecutive weeks with a point, split where the value is undefined.
+fn runs<T>(points: impl Iterator<Item = Option<T>>) -> Vec<Vec<T>> {
+    let mut runs = vec![Vec::new()];
+    for point in points {
+        match point {
+            Some(point) => runs.last_mut().unwrap().push(point),
+            None if runs.last().is_some_and(|run| !run.is_empty()) => runs.push(Vec::new()),
+            None => {}
+        }
+    }
+    runs.retain(|run| !run.is_empty());
+    runs
+}
+
+/// Chart with weeks on the X axis and a linear or logarithmic Y axis.
+type WeeklyChart<'a, 'b, Y> = ChartContext<'a, SVGBackend<'b>, Cartesian2d<RangedCoordf64, Y>>;
+
+/// Draws a line, with its confidence interval shaded if the style asks for it. Undefined
+/// values leave gaps, intervals are clipped to the chart.
+fn draw_line<Y: Ranged<ValueType = f64>>(
+    chart: &mut WeeklyChart<'_, '_, Y>,
+    line: &Line,
+    style: Style,
+    y_range: &Range<f64>,
+) -> anyhow::Result<()> {
+    let color = line.color;
+    if style.intervals {
+        let clip = |value: f64| value.clamp(y_range.start, y_range.end);
+        let bounds = runs(line.values.iter().enumerate().map(|(n, estimate)| {
+            (estimate.lower.is_finite() && estimate.upper.is_finite())
+                .then(|| (n as f64, clip(estimate.lower), clip(estimate.upper)))
+        }));
+        chart.draw_series(bounds.into_iter().map(|run| {
+            let upper = run.iter().map(|(x, _, upper)| (*x, *upper));
+            let lower = run.iter().rev().map(|(x, lower, _)| (*x, *lower));
+            Polygon::new(
+                upper.chain(lower).collect::<Vec<_>>(),
+                color.mix(0.2).filled(),
+            )
+        }))?;
+    }
+
+    let values = runs(line.values.iter().enumerate().map(|(n, estimate)| {
+        style
+            .shows(estimate.value)
+            .then_some((n as f64, estimate.value))
+    }));
+    chart
+        .draw_series(
+            values
+                .into_iter()
+                .map(|run| PathElement::new(run, color.stroke_width(2))),
+        )?
+        .label(&line.label)
+        .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled()));
     Ok(())
 }
 
-/// Draws generic chart based on a weekly data.
-fn quick_weekly_chart(
+/// Draws a chart of a metric of the reports, week by week, as an SVG file. Axes fit the
+/// values drawn.
+pub fn quick_weekly_chart(
     reports: &WeeklyReports,
-    path: String,
-    caption: String,
-    y_desc: String,
-    max_x: u32,
-    draw: impl Fn(&mut ChartContext<SVGBackend, Cartesian2d<RangedCoordu32, RangedCoordu32>>),
-) {
-    let area = SVGBackend::new(path.as_str(), (1024, 400)).into_drawing_area();
-    area.fill(&WHITE).unwrap();
+    path: PathBuf,
+    caption: &str,
+    y_desc: &str,
+    grouping: Grouping,
+    style: Style,
+) -> anyhow::Result<()> {
+    let lines = lines(reports, grouping, style)?;
 
-    let (top, bottom) = area.split_vertically(380);
-
-    bottom
-        .titled(
-            "Źródła danych: BASiW, ECDC, GUS",
-            ("sans-serif", 10).into_font().color(&BLACK.mix(0.5)),
-        )
-        .unwrap();
+    let area = SVGBackend::new(&path, (1024, 400)).into_drawing_area();
+    area.fill(&WHITE)?;
 
-    let x_axis = 0u32..(reports.0.len() - 1) as u32;
-    let y_axis = 0u32..max_x;
+    let (top, bottom) = area.split_vertically(380);
+    bottom.titled(
+        SOURCES,
+        ("sans-serif", 10).into_font().color(&BLACK.mix(0.5)),
+    )?;
 
-    let mut chart = ChartBuilder::on(&top)
+    let mut builder = ChartBuilder::on(&top);
+    builder
         .caption(
-            caption.clone(),
+            caption_of(reports, caption),
             ("sans-serif", 16).into_font().color(&BLACK),
         )
         .set_label_area_size(LabelAreaPosition::Left, 12.percent())
         .set_label_area_size(LabelAreaPosition::Bottom, 10.percent())
-        .margin(1.percent())
-        .build_cartesian_2d(x_axis.clone(), y_axis.clone())
-        .unwrap();
+        .margin(1.percent());
+    draw_chart(
+        &mut builder,
+        reports,
+        &lines,
+        y_desc,
+        style,
+        &value_range(&lines, style),
+        true,
+    )?;
+
+    area.present()?;
+    Ok(())
+}
+
+/// Age groups in a row of a faceted chart.
+const FACET_COLUMNS: usize = 5;
+
+/// Draws a panel per age group of the reports on a grid, each with a line per vaccination
+/// status, as an SVG file. Panels share their axes, so that age groups can be compared.
+pub fn faceted_weekly_chart(
+    reports: &WeeklyReports,
+    path: PathBuf,
+    caption: &str,
+    y_desc: &str,
+    statuses: &[(VaccinationStatus, Metric)],
+    style: Style,
+) -> anyhow::Result<()> {
+    let panels = reports
+        .age_groups
+        .iter()
+        .map(|age_group| {
+            status_lines(statuses, style, |metric| {
+                Ok(metric.of_age_group(reports, age_group))
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let y_axis = value_range(panels.iter().flatten(), style);
+
+    let rows = panels.len().div_ceil(FACET_COLUMNS).max(1);
+    let height = rows as u32 * 260 + 50;
+    let area = SVGBackend::new(&path, (FACET_COLUMNS as u32 * 320, height)).into_drawing_area();
+    area.fill(&WHITE)?;
+
+    let (top, bottom) = area.split_vertically(height - 20);
+    bottom.titled(
+        SOURCES,
+        ("sans-serif", 10).into_font().color(&BLACK.mix(0.5)),
+    )?;
+    let grid = top.titled(
+        &caption_of(reports, caption),
+        ("sans-serif", 16).into_font().color(&BLACK),
+    )?;
+
+    let cells = grid.split_evenly((rows, FACET_COLUMNS));
+    for (n, ((age_group, lines), cell)) in reports
+        .age_groups
+        .iter()
+        .zip(&panels)
+        .zip(&cells)
+        .enumerate()
+    {
+        let mut builder = ChartBuilder::on(cell);
+        builder
+            .caption(
+                age_group.to_string(),
+                ("sans-serif", 13).into_font().color(&BLACK),
+            )
+            .set_label_area_size(LabelAreaPosition::Left, 15.percent())
+            .set_label_area_size(LabelAreaPosition::Bottom, 15.percent())
+            .margin(2.percent());
+        // A single legend is enough, as every panel has the same lines.
+        draw_chart(&mut builder, reports, lines, y_desc, style, &y_axis, n == 0)?;
+    }
+
+    area.present()?;
+    Ok(())
+}
+
+const SOURCES: &str = "Źródła danych: BASiW, ECDC, GUS";
+
+/// Caption along with the stratum of the reports, unless they are of everyone.
+fn caption_of(reports: &WeeklyReports, caption: &str) -> String {
+    match reports.stratum.slug() {
+        Some(_) => format!("{} - {}", caption, reports.stratum),
+        None => caption.to_owned(),
+    }
+}
 
+/// Builds a chart of weeks with a linear or logarithmic Y axis, as the style asks, and
+/// draws lines onto it.
+fn draw_chart(
+    builder: &mut ChartBuilder<SVGBackend>,
+    reports: &WeeklyReports,
+    lines: &[Line],
+    y_desc: &str,
+    style: Style,
+    y_range: &Range<f64>,
+    legend: bool,
+) -> anyhow::Result<()> {
+    let x_axis = 0f64..(reports.weeks.len().saturating_sub(1).max(1)) as f64;
+    if style.log_scale {
+        let chart = builder.build_cartesian_2d(x_axis, y_range.clone().log_scale())?;
+        draw_lines(chart, reports, lines, y_desc, style, y_range, legend)
+    } else {
+        let chart = builder.build_cartesian_2d(x_axis, y_range.clone())?;
+        draw_lines(chart, reports, lines, y_desc, style, y_range, legend)
+    }
+}
+
+/// Draws axes, the reference line and lines with their legend onto a chart.
+fn draw_lines<'a, 'b: 'a, Y>(
+    mut chart: WeeklyChart<'a, 'b, Y>,
+    reports: &WeeklyReports,
+    lines: &[Line],
+    y_desc: &str,
+    style: Style,
+    y_range: &Range<f64>,
+    legend: bool,
+) -> anyhow::Result<()>
+where
+    Y: Ranged<ValueType = f64> + ValueFormatter<f64>,
+{
+    // Room for a week label every 80 pixels, so that small panels aren't crowded.
+    let width = chart.plotting_area().dim_in_pixel().0;
     chart
         .configure_mesh()
         .disable_mesh()
         .x_desc("Tydzień")
         .y_desc(y_desc)
-        .x_label_formatter(&|n| format!("{}", reports.0[*n as usize].0))
-        .draw()
-        .unwrap();
+        .x_labels((width / 80).max(2) as usize)
+        .x_label_formatter(&|x| week_label(reports, *x))
+        .y_label_formatter(&|y| value_label(*y))
+        .draw()?;
 
-    draw(&mut chart);
+    if let Some(reference) = style.reference {
+        let last = chart.x_range().end;
+        chart.draw_series(std::iter::once(PathElement::new(
+            vec![(0.0, reference), (last, reference)],
+            BLACK.mix(0.5).stroke_width(1),
+        )))?;
+    }
 
-    chart
-        .configure_series_labels()
-        .position(SeriesLabelPosition::UpperMiddle)
-        .border_style(&BLACK)
-        .draw()
-        .unwrap();
+    for line in lines {
+        draw_line(&mut chart, line, style, y_range)?;
+    }
+
+    if legend {
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::UpperMiddle)
+            .border_style(BLACK)
+            .draw()?;
+    }
+    Ok(())
+}
+
+/// Value on the Y axis without trailing zeros or rounding errors, e.g. `0.3` or `200`.
+fn value_label(y: f64) -> String {
+    format!("{}", (y * 1e6).round() / 1e6)
+}
 
-    area.present().unwrap();
+/// Week of a position on the X axis, empty between weeks.
+fn week_label(reports: &WeeklyReports, x: f64) -> String {
+    index_at(x)
+        .and_then(|n| reports.weeks.get(n))
+        .map(|(week, _)| week.to_string())
+        .unwrap_or_default()
 }
 
-pub(crate) fn draw_deaths(reports: &WeeklyReports) {
+/// Index of an item drawn at a whole position of an axis.
+fn index_at(position: f64) -> Option<usize> {
+    let n = position.round();
+    ((position - n).abs() <= 1e-6 && n >= 0.0).then_some(n as usize)
+}
+
+const PERCENT: Style = Style {
+    percent: true,
+    floor: None,
+    intervals: false,
+    log_scale: false,
+    reference: None,
+};
+
+/// Rate ratios on a logarithmic scale, so that halving and doubling the risk look alike,
+/// with their confidence intervals and a line at RR = 1.
+const RATIO: Style = Style {
+    percent: false,
+    floor: None,
+    intervals: true,
+    log_scale: true,
+    reference: Some(1.0),
+};
+
+/// Rates span orders of magnitude between age groups.
+const RATE: Style = Style {
+    percent: false,
+    floor: None,
+    intervals: false,
+    log_scale: true,
+    reference: None,
+};
+
+const DEATH_RATE_RATIOS: &[(VaccinationStatus, Metric)] = &[
+    (
+        VaccinationStatus::OneDose,
+        Metric::RateRatio(WeeklyReport::deaths_of_one_dose),
+    ),
+    (
+        VaccinationStatus::TwoDoses,
+        Metric::RateRatio(WeeklyReport::deaths_of_two_doses),
+    ),
+    (
+        VaccinationStatus::ThreeDoses,
+        Metric::RateRatio(WeeklyReport::deaths_of_three_doses),
+    ),
+];
+
+const CASE_RATE_RATIOS: &[(VaccinationStatus, Metric)] = &[
+    (
+        VaccinationStatus::OneDose,
+        Metric::RateRatio(WeeklyReport::cases_of_one_dose),
+    ),
+    (
+        VaccinationStatus::TwoDoses,
+        Metric::RateRatio(WeeklyReport::cases_of_two_doses),
+    ),
+    (
+        VaccinationStatus::ThreeDoses,
+        Metric::RateRatio(WeeklyReport::cases_of_three_doses),
+    ),
+];
+
+const CFRS: &[(VaccinationStatus, Metric)] = &[
+    (
+        VaccinationStatus::Unvaccinated,
+        Metric::Ratio(
+            |report| report.absolute_deaths.unvaccinated as f64,
+            |report| report.absolute_cases.unvaccinated as f64,
+        ),
+    ),
+    (
+        VaccinationStatus::OneDose,
+        Metric::Ratio(
+            |report| report.absolute_deaths.one_dose as f64,
+            |report| report.absolute_cases.one_dose as f64,
+        ),
+    ),
+    (
+        VaccinationStatus::TwoDoses,
+        Metric::Ratio(
+            |report| report.absolute_deaths.two_doses as f64,
+            |report| report.absolute_cases.two_doses as f64,
+        ),
+    ),
+    (
+        VaccinationStatus::ThreeDoses,
+        Metric::Ratio(
+            |report| report.absolute_deaths.three_doses as f64,
+            |report| report.absolute_cases.three_doses as f64,
+        ),
+    ),
+];
+
+const DEATHS_PER_MILLION: &[(VaccinationStatus, Metric)] = &[
+    (
+        VaccinationStatus::Unvaccinated,
+        Metric::Rate(|report| report.deaths_per_million.unvaccinated),
+    ),
+    (
+        VaccinationStatus::OneDose,
+        Metric::Rate(|report| report.deaths_per_million.one_dose),
+    ),
+    (
+        VaccinationStatus::TwoDoses,
+        Metric::Rate(|report| report.deaths_per_million.two_doses),
+    ),
+    (
+        VaccinationStatus::ThreeDoses,
+        Metric::Rate(|report| report.deaths_per_million.three_doses),
+    ),
+];
+
+/// Deaths of every age group, as `deaths.svg`.
+pub fn draw_deaths(reports: &WeeklyReports, output: &Path) -> anyhow::Result<()> {
     quick_weekly_chart(
         reports,
-        "output/deaths.svg".to_owned(),
-        "Zgony w poszczególnych grupach wiekowych (liczby bezwzględne)".to_owned(),
-        "Zgony".to_owned(),
-        1200,
-        |chart| {
-            for (chart_idx, age_group) in AGE_GROUPS.iter().enumerate() {
-                let color = Palette99::pick(chart_idx);
-                chart
-                    .draw_series(LineSeries::new(
-                        reports.0.iter().enumerate().map(|(n, (_, report))| {
-                            (
-                                n as u32,
-                                report.get(&age_group).unwrap().absolute_deaths.total() as u32,
-                            )
-                        }),
-                        color.stroke_width(2),
-                    ))
-                    .unwrap()
-                    .label(format!("{age_group}"))
-                    .legend(move |(x, y)| {
-                        Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled())
-                    });
-            }
-        },
-    );
+        output.join("deaths.svg"),
+        "Zgony w poszczególnych grupach wiekowych (liczby bezwzględne)",
+        "Zgony",
+        Grouping::ByAgeGroup(Metric::Value(|report| {
+            report.absolute_deaths.total() as f64
+        })),
+        Style::default(),
+    )
 }
 
-pub(crate) fn draw_risk_ratios(reports: &WeeklyReports) {
+/// Risk ratios of death pooled over age groups, as `risk_ratios.svg`.
+pub fn draw_risk_ratios(reports: &WeeklyReports, output: &Path) -> anyhow::Result<()> {
     quick_weekly_chart(
         reports,
-        "output/risk_ratios.svg".to_owned(),
-        "Ryzyko względne zgonu osób zaszczepionych (%)".to_owned(),
-        "%".to_owned(),
-        100,
-        |chart| {
-            let color = Palette99::pick(1);
-            chart
-                .draw_series(LineSeries::new(
-                    reports
-                        .mean(WeeklyReport::risk_ratio_of_two_doses)
-                        .iter()
-                        .enumerate()
-                        .map(|(n, (_, rr))| (n as u32, (rr * 100f64) as u32)),
-                    color.stroke_width(2),
-                ))
-                .unwrap()
-                .label("2 dawki")
-                .legend(move |(x, y)| {
-                    Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled())
-                });
-
-            let color = Palette99::pick(2);
-            chart
-                .draw_series(LineSeries::new(
-                    reports
-                        .mean(WeeklyReport::risk_ratio_of_three_doses)
-                        .iter()
-                        .enumerate()
-                        .map(|(n, (_, rr))| (n as u32, (rr * 100f64) as u32)),
-                    color.stroke_width(2),
-                ))
-                .unwrap()
-                .label("3 dawki")
-                .legend(move |(x, y)| {
-                    Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled())
-                });
-        },
-    );
-}
-
-pub(crate) fn draw_cfr(reports: &WeeklyReports) {
+        output.join("risk_ratios.svg"),
+        "Ryzyko względne zgonu osób zaszczepionych (Mantel-Haenszel)",
+        "RR",
+        Grouping::ByStatus(DEATH_RATE_RATIOS),
+        RATIO,
+    )
+}
+
+/// Risk ratios of death pooled over age groups of both sexes, so that they're adjusted for
+/// sex besides age, as `risk_ratios_sex_adjusted.svg`.
+pub fn draw_sex_adjusted_risk_ratios(
+    reports: &WeeklyReports,
+    by_sex: &[WeeklyReports],
+    output: &Path,
+) -> anyhow::Result<()> {
     quick_weekly_chart(
         reports,
-        "output/cfr.svg".to_owned(),
-        "Ryzyko zgonu po wykryciu zakażenia (CFR)".to_owned(),
-        "%".to_owned(),
-        20,
-        |chart| {
-            let color = Palette99::pick(0);
-            chart
-                .draw_series(LineSeries::new(
-                    reports
-                        .mean(WeeklyReport::cfr_unvaccinated)
-                        .iter()
-                        .enumerate()
-                        .map(|(n, (_, rr))| (n as u32, (rr * 100f64) as u32)),
-                    color.stroke_width(2),
-                ))
-                .unwrap()
-                .label("niezaszczepieni")
-                .legend(move |(x, y)| {
-                    Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled())
-                });
-
-            let color = Palette99::pick(1);
-            chart
-                .draw_series(LineSeries::new(
-                    reports
-                        .mean(WeeklyReport::cfr_two_doses)
-                        .iter()
-                        .enumerate()
-                        .map(|(n, (_, rr))| (n as u32, (rr * 100f64) as u32)),
-                    color.stroke_width(2),
-                ))
-                .unwrap()
-                .label("2 dawki")
-                .legend(move |(x, y)| {
-                    Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled())
-                });
-
-            let color = Palette99::pick(2);
-            chart
-                .draw_series(LineSeries::new(
-                    reports
-                        .mean(WeeklyReport::cfr_three_doses)
-                        .iter()
-                        .enumerate()
-                        .map(|(n, (_, rr))| (n as u32, (rr * 100f64) as u32)),
-                    color.stroke_width(2),
-                ))
-                .unwrap()
-                .label("3 dawki")
-                .legend(move |(x, y)| {
-                    Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled())
-                });
-        },
-    );
-}
-
-pub(crate) fn draw_case_risk_ratios(reports: &WeeklyReports) {
+        output.join("risk_ratios_sex_adjusted.svg"),
+        "Ryzyko względne zgonu osób zaszczepionych, skorygowane o płeć (Mantel-Haenszel)",
+        "RR",
+        Grouping::PooledByStatus(by_sex, DEATH_RATE_RATIOS),
+        RATIO,
+    )
+}
+
+/// Case fatality rates, as `cfr.svg`.
+pub fn draw_cfr(reports: &WeeklyReports, output: &Path) -> anyhow::Result<()> {
     quick_weekly_chart(
         reports,
-        "output/infection_risk_ratios.svg".to_owned(),
-        "Ryzyko względne pozytywnego testu u osób zaszczepionych (%)".to_owned(),
-        "%".to_owned(),
-        200,
-        |chart| {
-            let color = Palette99::pick(1);
-            chart
-                .draw_series(LineSeries::new(
-                    reports
-                        .mean(WeeklyReport::case_risk_ratio_of_two_doses)
-                        .iter()
-                        .enumerate()
-                        .map(|(n, (_, rr))| (n as u32, (rr * 100f64) as u32)),
-                    color.stroke_width(2),
-                ))
-                .unwrap()
-                .label("2 dawki")
-                .legend(move |(x, y)| {
-                    Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled())
-                });
-
-            let color = Palette99::pick(2);
-            chart
-                .draw_series(LineSeries::new(
-                    reports
-                        .mean(WeeklyReport::case_risk_ratio_of_three_doses)
-                        .iter()
-                        .enumerate()
-                        .map(|(n, (_, rr))| (n as u32, (rr * 100f64) as u32)),
-                    color.stroke_width(2),
-                ))
-                .unwrap()
-                .label("3 dawki")
-                .legend(move |(x, y)| {
-                    Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled())
-                });
-        },
-    );
-}
-
-pub(crate) fn draw_deaths_per_million_per_vaccination_status(reports: &WeeklyReports) {
+        output.join("cfr.svg"),
+        "Ryzyko zgonu po wykryciu zakażenia (CFR)",
+        "%",
+        Grouping::ByStatus(CFRS),
+        PERCENT,
+    )
+}
+
+/// Risk ratios of infection pooled over age groups, as `infection_risk_ratios.svg`.
+pub fn draw_case_risk_ratios(reports: &WeeklyReports, output: &Path) -> anyhow::Result<()> {
     quick_weekly_chart(
         reports,
-        "output/deaths_per_vaccination_status.svg".to_owned(),
-        "Zgony w przeliczeniu na million mieszkańców".to_owned(),
-        "Zgony".to_owned(),
-        1500,
-        |chart| {
-            let color = Palette99::pick(0);
-            chart
-                .draw_series(LineSeries::new(
-                    reports.0.iter().enumerate().map(|(n, (_, report))| {
-                        (
-                            n as u32,
-                            report
-                                .values()
-                                .map(|weekly_report| weekly_report.deaths_per_million.unvaccinated)
-                                .sum::<f64>() as u32,
-                        )
-                    }),
-                    color.stroke_width(2),
-                ))
-                .unwrap()
-                .label(format!("niezaszczepieni"))
-                .legend(move |(x, y)| {
-                    Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled())
-                });
-
-            let color = Palette99::pick(1);
-            chart
-                .draw_series(LineSeries::new(
-                    reports.0.iter().enumerate().map(|(n, (_, report))| {
-                        (
-                            n as u32,
-                            report
-                                .values()
-                                .map(|weekly_report| weekly_report.deaths_per_million.two_doses)
-                                .sum::<f64>() as u32,
-                        )
-                    }),
-                    color.stroke_width(2),
-                ))
-                .unwrap()
-                .label(format!("2 dawki"))
-                .legend(move |(x, y)| {
-                    Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled())
-                });
-
-            let color = Palette99::pick(2);
-            chart
-                .draw_series(LineSeries::new(
-                    reports.0.iter().enumerate().map(|(n, (_, report))| {
-                        (
-                            n as u32,
-                            report
-                                .values()
-                                .map(|weekly_report| weekly_report.deaths_per_million.three_doses)
-                                .sum::<f64>() as u32,
-                        )
-                    }),
-                    color.stroke_width(2),
-                ))
-                .unwrap()
-                .label(format!("3 dawki"))
-                .legend(move |(x, y)| {
-                    Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled())
-                });
-        },
-    );
-}
-
-pub(crate) fn draw_vaccinations_one_dose(reports: &WeeklyReports) {
+        output.join("infection_risk_ratios.svg"),
+        "Ryzyko względne pozytywnego testu u osób zaszczepionych (Mantel-Haenszel)",
+        "RR",
+        Grouping::ByStatus(CASE_RATE_RATIOS),
+        RATIO,
+    )
+}
+
+/// Age-standardized deaths per million, as `deaths_per_vaccination_status.svg`.
+pub fn draw_deaths_per_million_per_vaccination_status(
+    reports: &WeeklyReports,
+    output: &Path,
+) -> anyhow::Result<()> {
+    // Age groups lacking people of some status are left out of every status alike.
+    let excluded = reports
+        .excluded_weights()
+        .into_iter()
+        .map(|(_, weight)| weight)
+        .fold(0.0, f64::max);
     quick_weekly_chart(
         reports,
-        "output/vaccinations_one_dose.svg".to_owned(),
-        "Ilość osób zaszczepionych 1 dawką".to_owned(),
-        "Ilość".to_owned(),
-        10_000_000,
-        |chart| {
-            for (chart_idx, age_group) in AGE_GROUPS.iter().enumerate() {
-                let color = Palette99::pick(chart_idx);
-                chart
-                    .draw_series(LineSeries::new(
-                        reports.0.iter().enumerate().map(|(n, (_, report))| {
-                            (
-                                n as u32,
-                                report.get(&age_group).unwrap().vaccinated_people.one_dose as u32,
-                            )
-                        }),
-                        color.stroke_width(2),
-                    ))
-                    .unwrap()
-                    .label(format!("{age_group}"))
-                    .legend(move |(x, y)| {
-                        Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled())
-                    });
-            }
-        },
-    );
+        output.join("deaths_per_vaccination_status.svg"),
+        &format!(
+            "Zgony na milion mieszkańców, standaryzowane wiekiem ({}, pominięto do {:.0}% populacji)",
+            reports.standard_population,
+            excluded * 100.0
+        ),
+        "Zgony",
+        Grouping::ByStatus(DEATHS_PER_MILLION),
+        RATE,
+    )
+}
+
+/// Risk ratios of death of every age group side by side, as
+/// `risk_ratios_by_age_group.svg`.
+pub fn draw_risk_ratios_by_age_group(reports: &WeeklyReports, output: &Path) -> anyhow::Result<()> {
+    faceted_weekly_chart(
+        reports,
+        output.join("risk_ratios_by_age_group.svg"),
+        "Ryzyko względne zgonu osób zaszczepionych wg grup wiekowych",
+        "RR",
+        DEATH_RATE_RATIOS,
+        RATIO,
+    )
+}
+
+/// Risk ratios of infection of every age group side by side, as
+/// `infection_risk_ratios_by_age_group.svg`.
+pub fn draw_case_risk_ratios_by_age_group(
+    reports: &WeeklyReports,
+    output: &Path,
+) -> anyhow::Result<()> {
+    faceted_weekly_chart(
+        reports,
+        output.join("infection_risk_ratios_by_age_group.svg"),
+        "Ryzyko względne pozytywnego testu u osób zaszczepionych wg grup wiekowych",
+        "RR",
+        CASE_RATE_RATIOS,
+        RATIO,
+    )
+}
+
+/// Case fatality rates of every age group side by side, as `cfr_by_age_group.svg`.
+pub fn draw_cfr_by_age_group(reports: &WeeklyReports, output: &Path) -> anyhow::Result<()> {
+    faceted_weekly_chart(
+        reports,
+        output.join("cfr_by_age_group.svg"),
+        "Ryzyko zgonu po wykryciu zakażenia (CFR) wg grup wiekowych",
+        "%",
+        CFRS,
+        PERCENT,
+    )
+}
+
+/// Deaths per million of every age group side by side, as
+/// `deaths_per_million_by_age_group.svg`.
+pub fn draw_deaths_per_million_by_age_group(
+    reports: &WeeklyReports,
+    output: &Path,
+) -> anyhow::Result<()> {
+    faceted_weekly_chart(
+        reports,
+        output.join("deaths_per_million_by_age_group.svg"),
+        "Zgony na milion mieszkańców wg grup wiekowych",
+        "Zgony",
+        DEATHS_PER_MILLION,
+        RATE,
+    )
 }
 
-pub(crate) fn draw_vaccinations_two_doses(reports: &WeeklyReports) {
+/// File name, caption and the estimate drawn.
+type EstimateChart<'a> = (&'a str, &'a str, fn(&WeeklyReport) -> Estimate);
+
+/// Vaccine effectiveness against death and infection, with a line per age group.
+/// Negative effectiveness is drawn as zero.
+pub fn draw_effectiveness(reports: &WeeklyReports, output: &Path) -> anyhow::Result<()> {
+    let metrics: [EstimateChart; 6] = [
+        (
+            "effectiveness_deaths_1.svg",
+            "Skuteczność 1 dawki przeciwko zgonowi (%)",
+            WeeklyReport::effectiveness_of_one_dose,
+        ),
+        (
+            "effectiveness_deaths_2.svg",
+            "Skuteczność 2 dawek przeciwko zgonowi (%)",
+            WeeklyReport::effectiveness_of_two_doses,
+        ),
+        (
+            "effectiveness_deaths_3.svg",
+            "Skuteczność 3 dawek przeciwko zgonowi (%)",
+            WeeklyReport::effectiveness_of_three_doses,
+        ),
+        (
+            "effectiveness_cases_1.svg",
+            "Skuteczność 1 dawki przeciwko zakażeniu (%)",
+            WeeklyReport::case_effectiveness_of_one_dose,
+        ),
+        (
+            "effectiveness_cases_2.svg",
+            "Skuteczność 2 dawek przeciwko zakażeniu (%)",
+            WeeklyReport::case_effectiveness_of_two_doses,
+        ),
+        (
+            "effectiveness_cases_3.svg",
+            "Skuteczność 3 dawek przeciwko zakażeniu (%)",
+            WeeklyReport::case_effectiveness_of_three_doses,
+        ),
+    ];
+
+    for (file_name, caption, metric) in metrics {
+        quick_weekly_chart(
+            reports,
+            output.join(file_name),
+            caption,
+            "%",
+            Grouping::ByAgeGroup(Metric::Estimate(metric)),
+            Style {
+                floor: Some(0.0),
+                ..PERCENT
+            },
+        )?;
+    }
+    Ok(())
+}
+
+/// People with a single dose in every age group, as `vaccinations_one_dose.svg`.
+pub fn draw_vaccinations_one_dose(reports: &WeeklyReports, output: &Path) -> anyhow::Result<()> {
     quick_weekly_chart(
         reports,
-        "output/vaccinations_two_doses.svg".to_owned(),
-        "Ilość osób zaszczepionych 2 dawkami".to_owned(),
-        "Ilość".to_owned(),
-        10_000_000,
-        |chart| {
-            for (chart_idx, age_group) in AGE_GROUPS.iter().enumerate() {
-                let color = Palette99::pick(chart_idx);
-                chart
-                    .draw_series(LineSeries::new(
-                        reports.0.iter().enumerate().map(|(n, (_, report))| {
-                            (
-                                n as u32,
-                                report.get(&age_group).unwrap().vaccinated_people.two_doses as u32,
-                            )
-                        }),
-                        color.stroke_width(2),
-                    ))
-                    .unwrap()
-                    .label(format!("{age_group}"))
-                    .legend(move |(x, y)| {
-                        Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled())
-                    });
-            }
-        },
-    );
+        output.join("vaccinations_one_dose.svg"),
+        "Ilość osób zaszczepionych 1 dawką",
+        "Ilość",
+        Grouping::ByAgeGroup(Metric::Value(|report| {
+            report.vaccinated_people.one_dose as f64
+        })),
+        Style::default(),
+    )
 }
 
-pub(crate) fn draw_vaccinations_at_least_two_doses(reports: &WeeklyReports) {
+/// People with two doses in every age group, as `vaccinations_two_doses.svg`.
+pub fn draw_vaccinations_two_doses(reports: &WeeklyReports, output: &Path) -> anyhow::Result<()> {
     quick_weekly_chart(
         reports,
-        "output/vaccinations_at_least_two_doses.svg".to_owned(),
-        "Ilość osób zaszczepionych co najmniej 2 dawkami".to_owned(),
-        "Ilość".to_owned(),
-        10_000_000,
-        |chart| {
-            for (chart_idx, age_group) in AGE_GROUPS.iter().enumerate() {
-                let color = Palette99::pick(chart_idx);
-                chart
-                    .draw_series(LineSeries::new(
-                        reports.0.iter().enumerate().map(|(n, (_, report))| {
-                            (
-                                n as u32,
-                                report
-                                    .get(&age_group)
-                                    .unwrap()
-                                    .vaccinated_people
-                                    .at_least_two_doses as u32,
-                            )
-                        }),
-                        color.stroke_width(2),
-                    ))
-                    .unwrap()
-                    .label(format!("{age_group}"))
-                    .legend(move |(x, y)| {
-                        Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled())
-                    });
-            }
-        },
-    );
+        output.join("vaccinations_two_doses.svg"),
+        "Ilość osób zaszczepionych 2 dawkami",
+        "Ilość",
+        Grouping::ByAgeGroup(Metric::Value(|report| {
+            report.vaccinated_people.two_doses as f64
+        })),
+        Style::default(),
+    )
+}
+
+/// People with at least two doses in every age group, as
+/// `vaccinations_at_least_two_doses.svg`.
+pub fn draw_vaccinations_at_least_two_doses(
+    reports: &WeeklyReports,
+    output: &Path,
+) -> anyhow::Result<()> {
+    quick_weekly_chart(
+        reports,
+        output.join("vaccinations_at_least_two_doses.svg"),
+        "Ilość osób zaszczepionych co najmniej 2 dawkami",
+        "Ilość",
+        Grouping::ByAgeGroup(Metric::Value(|report| {
+            report.vaccinated_people.at_least_two_doses as f64
+        })),
+        Style::default(),
+    )
+}
+
+/// Colours of heatmaps from the lowest value to the highest, after viridis.
+const HEATMAP_COLORS: &[RGBColor] = &[
+    RGBColor(68, 1, 84),
+    RGBColor(59, 82, 139),
+    RGBColor(33, 145, 140),
+    RGBColor(94, 201, 98),
+    RGBColor(253, 231, 37),
+];
+
+/// Colour of cells with an undefined value.
+const UNDEFINED: RGBColor = RGBColor(220, 220, 220);
+
+/// Colour of a position on the heatmap scale, from 0 to 1.
+fn heatmap_color(position: f64) -> RGBColor {
+    let position = position.clamp(0.0, 1.0) * (HEATMAP_COLORS.len() - 1) as f64;
+    let n = (position.floor() as usize).min(HEATMAP_COLORS.len() - 2);
+    let (from, to) = (HEATMAP_COLORS[n], HEATMAP_COLORS[n + 1]);
+    let share = position - n as f64;
+    let mix = |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * share).round() as u8;
+    RGBColor(mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+}
+
+/// Values mapped onto colours of a heatmap, linearly or logarithmically.
+struct ColorScale {
+    range: Range<f64>,
+    log_scale: bool,
+}
+
+impl ColorScale {
+    /// Scale from the lowest to the highest of values which can be drawn.
+    fn new(values: impl Iterator<Item = f64>, log_scale: bool) -> Self {
+        let style = Style {
+            log_scale,
+            ..Style::default()
+        };
+        let (min, max) = values
+            .filter(|value| style.shows(*value))
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+                (min.min(value), max.max(value))
+            });
+        let range = match (min.is_finite(), max > min) {
+            (false, _) if log_scale => 0.1..10.0,
+            (false, _) => 0.0..1.0,
+            (true, false) if log_scale => min / 2.0..min * 2.0,
+            (true, false) => min - 0.5..min + 0.5,
+            (true, true) => min..max,
+        };
+        Self { range, log_scale }
+    }
+
+    fn scaled(&self, value: f64) -> f64 {
+        if self.log_scale {
+            value.ln()
+        } else {
+            value
+        }
+    }
+
+    /// Position of a value on the scale, from 0 to 1.
+    fn position(&self, value: f64) -> f64 {
+        let start = self.scaled(self.range.start);
+        (self.scaled(value) - start) / (self.scaled(self.range.end) - start)
+    }
+
+    /// Value at a position of the scale.
+    fn value_at(&self, position: f64) -> f64 {
+        let (start, end) = (self.scaled(self.range.start), self.scaled(self.range.end));
+        let scaled = start + (end - start) * position;
+        if self.log_scale {
+            scaled.exp()
+        } else {
+            scaled
+        }
+    }
+
+    fn color(&self, value: f64) -> RGBColor {
+        let shown = value.is_finite() && (!self.log_scale || value > 0.0);
+        if shown {
+            heatmap_color(self.position(value))
+        } else {
+            UNDEFINED
+        }
+    }
+}
+
+/// Metrics drawn as heatmaps unless others are asked for.
+pub const DEFAULT_HEATMAPS: &[MetricName] = &[
+    MetricName {
+        metric: "deaths_per_million",
+        status: "unvaccinated",
+    },
+    MetricName {
+        metric: "death_rate_ratio",
+        status: "two_doses",
+    },
+    MetricName {
+        metric: "coverage",
+        status: "two_doses",
+    },
+];
+
+/// Draws a metric of every week and age group as a grid of coloured cells, the oldest on
+/// top, with a colour legend, as `heatmap_{metric}_{status}.svg`, on the scale of the
+/// metric. Cells without a value are grey.
+pub fn draw_heatmap(
+    reports: &WeeklyReports,
+    name: MetricName,
+    output: &Path,
+) -> anyhow::Result<()> {
+    let log_scale = name.scale() == Scale::Logarithmic;
+    let cells: Vec<(f64, f64, f64)> = reports
+        .weeks
+        .iter()
+        .enumerate()
+        .flat_map(|(x, (_, by_age_group))| {
+            reports
+                .age_groups
+                .iter()
+                .enumerate()
+                .map(move |(y, age_group)| {
+                    (x as f64, y as f64, name.value(&by_age_group[age_group]))
+                })
+        })
+        .collect();
+    let scale = ColorScale::new(cells.iter().map(|(_, _, value)| *value), log_scale);
+
+    let path = output.join(format!("heatmap_{}_{}.svg", name.metric, name.status));
+    let area = SVGBackend::new(&path, (1024, 400)).into_drawing_area();
+    area.fill(&WHITE)?;
+
+    let (top, bottom) = area.split_vertically(380);
+    bottom.titled(
+        SOURCES,
+        ("sans-serif", 10).into_font().color(&BLACK.mix(0.5)),
+    )?;
+    let top = top.titled(
+        &caption_of(reports, &format!("{} wg tygodnia i grupy wiekowej", name)),
+        ("sans-serif", 16).into_font().color(&BLACK),
+    )?;
+    let (map, legend) = top.split_horizontally(920);
+
+    let weeks = reports.weeks.len().max(1) as f64;
+    let age_groups = reports.age_groups.len().max(1) as f64;
+    let mut chart = ChartBuilder::on(&map)
+        .set_label_area_size(LabelAreaPosition::Left, 8.percent())
+        .set_label_area_size(LabelAreaPosition::Bottom, 12.percent())
+        .margin(1.percent())
+        .build_cartesian_2d(-0.5..weeks - 0.5, -0.5..age_groups - 0.5)?;
+
+    let width = chart.plotting_area().dim_in_pixel().0;
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_desc("Tydzień")
+        .y_desc("Wiek")
+        .x_labels((width / 80).max(2) as usize)
+        .x_label_formatter(&|x| week_label(reports, *x))
+        .y_labels(reports.age_groups.len())
+        .y_label_formatter(&|y| {
+            index_at(*y)
+                .and_then(|n| reports.age_groups.get(n))
+                .map(|age_group| age_group.to_string())
+                .unwrap_or_default()
+        })
+        .draw()?;
+
+    chart.draw_series(cells.iter().map(|(x, y, value)| {
+        Rectangle::new(
+            [(x - 0.5, y - 0.5), (x + 0.5, y + 0.5)],
+            scale.color(*value).filled(),
+        )
+    }))?;
+
+    let mut builder = ChartBuilder::on(&legend);
+    builder
+        .set_label_area_size(LabelAreaPosition::Right, 60.percent())
+        .margin_top(1.percent())
+        .margin_bottom(12.percent());
+    if log_scale {
+        let chart = builder.build_cartesian_2d(0f64..1f64, scale.range.clone().log_scale())?;
+        draw_color_legend(chart, &scale)?;
+    } else {
+        let chart = builder.build_cartesian_2d(0f64..1f64, scale.range.clone())?;
+        draw_color_legend(chart, &scale)?;
+    }
+
+    area.present()?;
+    Ok(())
+}
+
+/// Draws a gradient of the scale with values along it.
+fn draw_color_legend<'a, 'b: 'a, Y>(
+    mut chart: ChartContext<'a, SVGBackend<'b>, Cartesian2d<RangedCoordf64, Y>>,
+    scale: &ColorScale,
+) -> anyhow::Result<()>
+where
+    Y: Ranged<ValueType = f64> + ValueFormatter<f64>,
+{
+    const STEPS: usize = 100;
+    chart.draw_series((0..STEPS).map(|step| {
+        let from = step as f64 / STEPS as f64;
+        let to = (step + 1) as f64 / STEPS as f64;
+        Rectangle::new(
+            [(0.0, scale.value_at(from)), (1.0, scale.value_at(to))],
+            heatmap_color((from + to) / 2.0).filled(),
+        )
+    }))?;
+
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .disable_x_axis()
+        .y_label_formatter(&|y| value_label(*y))
+        .draw()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(values: &[f64]) -> Line {
+        Line {
+            label: String::new(),
+            color: BLACK.to_rgba(),
+            values: values.iter().map(|value| exact(*value)).collect(),
+        }
+    }
+
+    #[test]
+    fn undefined_values_split_lines() {
+        let runs = runs([Some(1), None, None, Some(2), Some(3), None].into_iter());
+
+        assert_eq!(runs, [vec![1], vec![2, 3]]);
+    }
+
+    #[test]
+    fn range_fits_every_finite_value() {
+        let range = value_range(
+            &[line(&[0.5, f64::NAN]), line(&[2.0, f64::INFINITY])],
+            Style::default(),
+        );
+
+        assert_eq!(range.start, 0.0);
+        assert!((range.end - 2.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn range_extends_below_zero_for_negative_values() {
+        let range = value_range(&[line(&[-1.0, 1.0])], Style::default());
+
+        assert!((range.start + 1.1).abs() < 1e-9);
+        assert!((range.end - 1.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn log_scale_leaves_out_values_which_are_not_positive() {
+        let range = value_range(&[line(&[0.0, 0.1, 0.4, -1.0])], RATIO);
+
+        // 1 is the reference, a factor of ten above the lowest value.
+        let margin = 10f64.powf(0.05);
+        assert!((range.start - 0.1 / margin).abs() < 1e-9);
+        assert!((range.end - margin).abs() < 1e-9);
+    }
+
+    #[test]
+    fn heatmap_colors_span_the_palette() {
+        assert_eq!(heatmap_color(0.0), HEATMAP_COLORS[0]);
+        assert_eq!(heatmap_color(0.5), HEATMAP_COLORS[2]);
+        assert_eq!(heatmap_color(1.0), HEATMAP_COLORS[4]);
+        assert_eq!(heatmap_color(2.0), HEATMAP_COLORS[4]);
+    }
+
+    #[test]
+    fn logarithmic_color_scale_puts_the_geometric_mean_halfway() {
+        let scale = ColorScale::new([0.0, 0.1, 10.0, f64::NAN].into_iter(), true);
+
+        assert_eq!(scale.range, 0.1..10.0);
+        assert!((scale.position(1.0) - 0.5).abs() < 1e-9);
+        assert!((scale.value_at(0.5) - 1.0).abs() < 1e-9);
+        assert_eq!(scale.color(0.0), UNDEFINED);
+    }
 }
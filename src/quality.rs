@@ -0,0 +1,180 @@
+//! Accounting of rows dropped while loading source files.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::Serialize;
+
+/// Rows of every reason kept as examples.
+const SAMPLES: usize = 5;
+
+/// Dropped row kept as an example of its reason.
+#[derive(Debug, Serialize)]
+pub struct Sample {
+    /// Line of the source file, if known.
+    pub line: Option<u64>,
+    /// Why exactly the row was dropped.
+    pub message: String,
+    /// Fields of the row.
+    pub row: Vec<String>,
+}
+
+/// Rows dropped for the same reason.
+#[derive(Debug, Default, Serialize)]
+pub struct Reason {
+    /// Rows dropped.
+    pub records: usize,
+    /// People (deaths, infections, vaccinated) those rows report.
+    pub reported: usize,
+    /// First few of the rows.
+    pub samples: Vec<Sample>,
+}
+
+/// Account of rows of a source file which were read and which of them were dropped.
+#[derive(Debug, Serialize)]
+pub struct Rejections {
+    /// Source file.
+    pub file: PathBuf,
+    /// Rows read, including dropped ones.
+    pub records: usize,
+    /// People reported by every row read.
+    pub reported: usize,
+    /// Dropped rows by reason.
+    pub reasons: BTreeMap<String, Reason>,
+}
+
+impl Rejections {
+    pub(crate) fn new(path: &Path) -> Self {
+        Self {
+            file: path.to_owned(),
+            records: 0,
+            reported: 0,
+            reasons: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn accept(&mut self, reported: usize) {
+        self.records += 1;
+        self.reported += reported;
+    }
+
+    pub(crate) fn reject(&mut self, reason: String, reported: usize, sample: Sample) {
+        self.accept(reported);
+        let reason = self.reasons.entry(reason).or_default();
+        reason.records += 1;
+        reason.reported += reported;
+        if reason.samples.len() < SAMPLES {
+            reason.samples.push(sample);
+        }
+    }
+
+    /// Rows dropped for any reason.
+    pub fn dropped_records(&self) -> usize {
+        self.reasons.values().map(|reason| reason.records).sum()
+    }
+
+    /// People reported by dropped rows.
+    pub fn dropped_reported(&self) -> usize {
+        self.reasons.values().map(|reason| reason.reported).sum()
+    }
+
+    /// Share of reported people lost with dropped rows.
+    pub fn share_lost(&self) -> f64 {
+        self.dropped_reported() as f64 / self.reported as f64
+    }
+}
+
+impl Display for Rejections {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: odrzucono {} z {} rekordów, {} z {} zgłoszonych osób ({:.3}%)",
+            self.file.display(),
+            self.dropped_records(),
+            self.records,
+            self.dropped_reported(),
+            self.reported,
+            self.share_lost() * 100.0
+        )?;
+        for (name, reason) in &self.reasons {
+            write!(
+                f,
+                "\n  {}: {} rekordów, {} osób",
+                name, reason.records, reason.reported
+            )?;
+            for sample in &reason.samples {
+                write!(f, "\n    ")?;
+                if let Some(line) = sample.line {
+                    write!(f, "wiersz {}: ", line)?;
+                }
+                write!(f, "{} ({})", sample.message, sample.row.join(";"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rows dropped from every source file, so it can be judged whether they bias the results.
+#[derive(Debug, Serialize)]
+pub struct DataQuality<'a> {
+    /// Rows of the BASiW deaths.
+    pub deaths: &'a Rejections,
+    /// Rows of the BASiW infections.
+    pub cases: &'a Rejections,
+    /// Rows of the ECDC vaccinations.
+    pub vaccinations: &'a Rejections,
+    /// Share of reported deaths which didn't make it into the results.
+    pub deaths_lost: f64,
+    /// Weeks and age groups with more people vaccinated than living in them, whose
+    /// unvaccinated people were clamped at zero.
+    pub clamped_cells: Vec<String>,
+}
+
+impl<'a> DataQuality<'a> {
+    /// Account of rows dropped from given files and of clamped cells.
+    pub fn new(
+        deaths: &'a Rejections,
+        cases: &'a Rejections,
+        vaccinations: &'a Rejections,
+        clamped_cells: Vec<String>,
+    ) -> Self {
+        Self {
+            deaths,
+            cases,
+            vaccinations,
+            deaths_lost: deaths.share_lost(),
+            clamped_cells,
+        }
+    }
+
+    /// Writes the account as `data_quality.json` into a given directory.
+    pub fn write(&self, output: &Path) -> anyhow::Result<()> {
+        let path = output.join("data_quality.json");
+        let file = std::fs::File::create(&path)
+            .with_context(|| format!("can't create {}", path.display()))?;
+        serde_json::to_writer_pretty(file, self)
+            .with_context(|| format!("can't write {}", path.display()))
+    }
+}
+
+impl Display for DataQuality<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Jakość danych:")?;
+        writeln!(f, "{}", self.deaths)?;
+        writeln!(f, "{}", self.cases)?;
+        writeln!(f, "{}", self.vaccinations)?;
+        write!(
+            f,
+            "Utracone zgony: {:.3}% zgłoszonych",
+            self.deaths_lost * 100.0
+        )?;
+        for cell in &self.clamped_cells {
+            write!(f, "\nPominięto niezaszczepionych: {}", cell)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+//! Voivodeships, the regions results can be stratified by.
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Deserializer};
+
+use crate::errors::InvalidValue;
+
+/// Polish voivodeship. BASiW identifies them with TERYT codes, ECDC with NUTS 2 regions.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub enum Voivodeship {
+    Dolnoslaskie,
+    KujawskoPomorskie,
+    Lubelskie,
+    Lubuskie,
+    Lodzkie,
+    Malopolskie,
+    Mazowieckie,
+    Opolskie,
+    Podkarpackie,
+    Podlaskie,
+    Pomorskie,
+    Slaskie,
+    Swietokrzyskie,
+    WarminskoMazurskie,
+    Wielkopolskie,
+    Zachodniopomorskie,
+}
+
+use Voivodeship::*;
+
+/// TERYT code, NUTS code and name of every voivodeship. ECDC reports the whole Mazowieckie
+/// as `PL92X`, even though it's split into PL91 and PL92 in NUTS 2021.
+const VOIVODESHIPS: &[(Voivodeship, u8, &str, &str)] = &[
+    (Dolnoslaskie, 2, "PL51", "dolnośląskie"),
+    (KujawskoPomorskie, 4, "PL61", "kujawsko-pomorskie"),
+    (Lubelskie, 6, "PL81", "lubelskie"),
+    (Lubuskie, 8, "PL43", "lubuskie"),
+    (Lodzkie, 10, "PL71", "łódzkie"),
+    (Malopolskie, 12, "PL21", "małopolskie"),
+    (Mazowieckie, 14, "PL92X", "mazowieckie"),
+    (Opolskie, 16, "PL52", "opolskie"),
+    (Podkarpackie, 18, "PL82", "podkarpackie"),
+    (Podlaskie, 20, "PL84", "podlaskie"),
+    (Pomorskie, 22, "PL63", "pomorskie"),
+    (Slaskie, 24, "PL22", "śląskie"),
+    (Swietokrzyskie, 26, "PL72", "świętokrzyskie"),
+    (WarminskoMazurskie, 28, "PL62", "warmińsko-mazurskie"),
+    (Wielkopolskie, 30, "PL41", "wielkopolskie"),
+    (Zachodniopomorskie, 32, "PL42", "zachodniopomorskie"),
+];
+
+impl Voivodeship {
+    /// Every voivodeship, in alphabetical order.
+    pub fn all() -> impl Iterator<Item = Voivodeship> {
+        VOIVODESHIPS.iter().map(|(voivodeship, ..)| *voivodeship)
+    }
+
+    fn entry(&self) -> &'static (Voivodeship, u8, &'static str, &'static str) {
+        VOIVODESHIPS
+            .iter()
+            .find(|(voivodeship, ..)| voivodeship == self)
+            .unwrap()
+    }
+
+    /// TERYT code, e.g. 30 for wielkopolskie.
+    pub fn teryt(&self) -> u8 {
+        self.entry().1
+    }
+
+    /// NUTS 2 region, e.g. PL41 for wielkopolskie.
+    pub fn nuts(&self) -> &'static str {
+        self.entry().2
+    }
+
+    /// Voivodeship of a TERYT code.
+    pub fn from_teryt(teryt: u8) -> Option<Self> {
+        VOIVODESHIPS
+            .iter()
+            .find(|(_, code, ..)| *code == teryt)
+            .map(|(voivodeship, ..)| *voivodeship)
+    }
+
+    /// Voivodeship of a NUTS 2 region.
+    pub fn from_nuts(nuts: &str) -> Option<Self> {
+        VOIVODESHIPS
+            .iter()
+            .find(|(_, _, code, _)| *code == nuts)
+            .map(|(voivodeship, ..)| *voivodeship)
+    }
+}
+
+impl Display for Voivodeship {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.entry().3)
+    }
+}
+
+impl FromStr for Voivodeship {
+    type Err = anyhow::Error;
+
+    /// Accepts TERYT code (`30`), NUTS code (`PL41`) or a name (`wielkopolskie`).
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        text.parse()
+            .ok()
+            .and_then(Voivodeship::from_teryt)
+            .or_else(|| Voivodeship::from_nuts(text))
+            .or_else(|| Voivodeship::all().find(|voivodeship| voivodeship.to_string() == text))
+            .ok_or_else(|| anyhow!("unknown voivodeship: '{}'", text))
+    }
+}
+
+pub(crate) fn deserialize_teryt<'a, D>(deserializer: D) -> Result<Voivodeship, D::Error>
+where
+    D: Deserializer<'a>,
+{
+    let text: &str = Deserialize::deserialize(deserializer)?;
+    text.parse()
+        .ok()
+        .and_then(Voivodeship::from_teryt)
+        .ok_or_else(|| InvalidValue::Voivodeship.error())
+}
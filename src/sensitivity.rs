@@ -0,0 +1,70 @@
+//! Sensitivity of rate ratios to the lag between a dose and the protection it gives.
+
+use crate::{
+    datasets::Datasets,
+    demographics::StandardPopulation,
+    intervals::{mantel_haenszel, Comparison, Estimate},
+    AgeGroup, Stratum, WeeklyReport, WeeklyReports, YearWeek,
+};
+
+/// Lags compared against each other, in weeks. Two weeks stand for the usual 14 days to
+/// full protection.
+pub const PROTECTION_LAGS: &[usize] = &[0, 1, 2, 4];
+
+/// Rate ratios pooled over every week and age group, with people counted as vaccinated
+/// `lag` weeks after their dose.
+#[derive(Debug, Clone, Copy)]
+pub struct LagSensitivity {
+    /// Weeks after a dose before people are counted as having it.
+    pub lag: usize,
+    /// Risk of death of people with two doses relative to the unvaccinated.
+    pub risk_ratio_of_two_doses: Estimate,
+    /// Risk of death of people with a booster relative to the unvaccinated.
+    pub risk_ratio_of_three_doses: Estimate,
+    /// Risk of infection of people with two doses relative to the unvaccinated.
+    pub case_risk_ratio_of_two_doses: Estimate,
+    /// Risk of infection of people with a booster relative to the unvaccinated.
+    pub case_risk_ratio_of_three_doses: Estimate,
+}
+
+/// Mantel-Haenszel rate ratio pooled over weeks as well as age groups.
+fn pooled(reports: &WeeklyReports, comparison: fn(&WeeklyReport) -> Comparison) -> Estimate {
+    mantel_haenszel(
+        reports
+            .weeks()
+            .iter()
+            .flat_map(|(_, by_age_group)| by_age_group.values().map(comparison)),
+    )
+}
+
+/// Rate ratios of a stratum with every lag of `PROTECTION_LAGS`.
+pub fn protection_lags(
+    datasets: &Datasets,
+    weeks: &[YearWeek],
+    age_groups: &[AgeGroup],
+    stratum: Stratum,
+) -> anyhow::Result<Vec<LagSensitivity>> {
+    PROTECTION_LAGS
+        .iter()
+        .map(|lag| {
+            // Pooled ratios don't depend on the standard population.
+            let reports = datasets.weekly_reports_with_lag(
+                weeks,
+                age_groups,
+                stratum,
+                StandardPopulation::default(),
+                *lag,
+            )?;
+            Ok(LagSensitivity {
+                lag: *lag,
+                risk_ratio_of_two_doses: pooled(&reports, WeeklyReport::deaths_of_two_doses),
+                risk_ratio_of_three_doses: pooled(&reports, WeeklyReport::deaths_of_three_doses),
+                case_risk_ratio_of_two_doses: pooled(&reports, WeeklyReport::cases_of_two_doses),
+                case_risk_ratio_of_three_doses: pooled(
+                    &reports,
+                    WeeklyReport::cases_of_three_doses,
+                ),
+            })
+        })
+        .collect()
+}
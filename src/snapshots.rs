@@ -0,0 +1,154 @@
+//! Snapshots of BASiW datasets and picking files of them.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context};
+use chrono::NaiveDate;
+
+/// BASiW publishes its datasets as cumulative snapshots, with the date (and time) of
+/// the publication in the file name, e.g. `ewp_dsh_zgony_po_szczep_202202010941.csv`.
+pub(crate) const DEATHS_PREFIX: &str = "ewp_dsh_zgony_po_szczep_";
+pub(crate) const CASES_PREFIX: &str = "ewp_dsh_zakazenia_po_szczepieniu_";
+
+/// Date of a BASiW snapshot. Time of the publication is ignored, since deaths and
+/// infections of the same snapshot are published a minute or so apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Snapshot(NaiveDate);
+
+impl Display for Snapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.format("%Y%m%d"))
+    }
+}
+
+impl FromStr for Snapshot {
+    type Err = anyhow::Error;
+
+    /// Accepts `20211214` as well as `2021-12-14`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        NaiveDate::parse_from_str(text, "%Y%m%d")
+            .or_else(|_| NaiveDate::parse_from_str(text, "%Y-%m-%d"))
+            .map(Snapshot)
+            .map_err(|_| anyhow!("bad snapshot date: '{}', expected e.g. 20211214", text))
+    }
+}
+
+impl Snapshot {
+    /// Extracts the snapshot date from a file name like `<prefix>202202010941.csv`.
+    fn from_file_name(prefix: &str, file_name: &str) -> Option<Self> {
+        let suffix = file_name.strip_prefix(prefix)?.strip_suffix(".csv")?;
+        suffix.get(..8)?.parse().ok()
+    }
+}
+
+/// Finds every snapshot of a given dataset in `dir`. If there are several files of the
+/// same date, the latest one (by its time suffix) wins.
+pub(crate) fn find(dir: &Path, prefix: &str) -> anyhow::Result<BTreeMap<Snapshot, PathBuf>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("can't read {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    files.sort();
+
+    Ok(files
+        .into_iter()
+        .filter_map(|path| {
+            let snapshot = Snapshot::from_file_name(prefix, path.file_name()?.to_str()?)?;
+            Some((snapshot, path))
+        })
+        .collect())
+}
+
+/// BASiW files making up a single snapshot.
+#[derive(Debug, Clone)]
+pub struct SnapshotFiles {
+    /// `None` if paths were given explicitly.
+    pub snapshot: Option<Snapshot>,
+    /// File with deaths.
+    pub deaths: PathBuf,
+    /// File with infections.
+    pub cases: PathBuf,
+}
+
+/// Picks BASiW files to analyze. Explicit paths take precedence, then requested snapshot
+/// dates. If neither is given, the newest snapshot having both deaths and infections is used.
+pub fn resolve(
+    dir: &Path,
+    deaths: Option<&Path>,
+    cases: Option<&Path>,
+    requested: &[Snapshot],
+) -> anyhow::Result<Vec<SnapshotFiles>> {
+    if let (Some(deaths), Some(cases)) = (deaths, cases) {
+        return Ok(vec![SnapshotFiles {
+            snapshot: None,
+            deaths: deaths.to_owned(),
+            cases: cases.to_owned(),
+        }]);
+    }
+
+    let deaths_snapshots = find(dir, DEATHS_PREFIX)?;
+    let cases_snapshots = find(dir, CASES_PREFIX)?;
+
+    let files_of = |snapshot: Snapshot| -> Option<SnapshotFiles> {
+        Some(SnapshotFiles {
+            snapshot: Some(snapshot),
+            deaths: match deaths {
+                Some(deaths) => deaths.to_owned(),
+                None => deaths_snapshots.get(&snapshot)?.clone(),
+            },
+            cases: match cases {
+                Some(cases) => cases.to_owned(),
+                None => cases_snapshots.get(&snapshot)?.clone(),
+            },
+        })
+    };
+
+    let describe = |snapshots: &BTreeMap<Snapshot, PathBuf>| {
+        if snapshots.is_empty() {
+            "none".to_owned()
+        } else {
+            snapshots
+                .keys()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    };
+
+    if requested.is_empty() {
+        let dates: BTreeSet<Snapshot> = deaths_snapshots
+            .keys()
+            .chain(cases_snapshots.keys())
+            .copied()
+            .collect();
+        let newest = dates.iter().rev().find_map(|snapshot| files_of(*snapshot));
+        newest.map(|files| vec![files]).ok_or_else(|| {
+            anyhow!(
+                "no complete BASiW snapshot in {} (deaths: {}, infections: {}), see fetch-data.sh",
+                dir.display(),
+                describe(&deaths_snapshots),
+                describe(&cases_snapshots)
+            )
+        })
+    } else {
+        requested
+            .iter()
+            .map(|snapshot| {
+                files_of(*snapshot).ok_or_else(|| {
+                    anyhow!(
+                        "snapshot {} is not complete in {} (deaths: {}, infections: {})",
+                        snapshot,
+                        dir.display(),
+                        describe(&deaths_snapshots),
+                        describe(&cases_snapshots)
+                    )
+                })
+            })
+            .collect()
+    }
+}
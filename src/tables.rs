@@ -1,15 +1,39 @@
-use prettytable::{Cell, Row};
+//! Tables of weekly reports as they are printed, with Polish headers and rounded numbers.
 
-use crate::{AgeGroup, WeeklyReport, YearWeek};
+use std::path::Path;
 
-pub(crate) fn print_stats_for_age_group(
-    age_group: AgeGroup,
+use anyhow::Context;
+use prettytable::{Cell, Row, Table};
+
+use crate::{
+    deaths::VaccinationStatus,
+    intervals::{Comparison, Estimate},
+    sensitivity::LagSensitivity,
+    waning::Waning,
+    AgeGroup, WeeklyReport, WeeklyReports, YearWeek,
+};
+
+type Column<'a> = (&'a str, &'a dyn Fn(YearWeek, WeeklyReport) -> String);
+type WaningColumn<'a> = (&'a str, &'a dyn Fn(&Waning) -> String);
+type LagColumn<'a> = (&'a str, &'a dyn Fn(&LagSensitivity) -> String);
+type PooledColumn<'a> = (&'a str, &'a dyn Fn(&WeeklyReport) -> Comparison);
+
+/// Value of an estimate rounded, or a dash if it's undefined.
+fn rounded(value: f64, decimals: usize) -> String {
+    if value.is_nan() {
+        return "–".to_owned();
+    }
+    format!("{:.*}", decimals, value)
+}
+
+/// Table of people, infections, deaths and their rates of an age group, week by week.
+pub fn stats_for_age_group(
     weekly_reports: impl Iterator<Item = (YearWeek, WeeklyReport)>,
-) {
-    let mut table = prettytable::Table::new();
+) -> Table {
+    let mut table = Table::new();
     table.set_format(*prettytable::format::consts::FORMAT_CLEAN);
 
-    let data: Vec<(&str, &dyn Fn(YearWeek, WeeklyReport) -> String)> = vec![
+    let data: Vec<Column> = vec![
         ("Tydzień", &|week, _| format!("{}", week)),
         ("Niezasz.", &|_, report| {
             format!("{}", report.unvaccinated_people)
@@ -32,6 +56,9 @@ pub(crate) fn print_stats_for_age_group(
         ("Zak. (NZ)", &|_, report| {
             format!("{}", report.absolute_cases.unvaccinated)
         }),
+        ("Zak. (1)", &|_, report| {
+            format!("{}", report.absolute_cases.one_dose)
+        }),
         ("Zak. (2)", &|_, report| {
             format!("{}", report.absolute_cases.two_doses)
         }),
@@ -42,16 +69,35 @@ pub(crate) fn print_stats_for_age_group(
         ("Zak./mln (NZ)", &|_, report| {
             format!("{:.2}", report.cases_per_million.unvaccinated)
         }),
+        ("Zak./mln (1)", &|_, report| {
+            format!("{:.2}", report.cases_per_million.one_dose)
+        }),
         ("Zak./mln (2)", &|_, report| {
             format!("{:.2}", report.cases_per_million.two_doses)
         }),
         ("Zak./mln (3)", &|_, report| {
             format!("{:.2}", report.cases_per_million.three_doses)
         }),
+        // Cases per 100k person-weeks
+        ("Zak./100 tys. osobotyg. (NZ)", &|_, report| {
+            format!("{:.2}", report.cases_per_100k_person_weeks.unvaccinated)
+        }),
+        ("Zak./100 tys. osobotyg. (1)", &|_, report| {
+            format!("{:.2}", report.cases_per_100k_person_weeks.one_dose)
+        }),
+        ("Zak./100 tys. osobotyg. (2)", &|_, report| {
+            format!("{:.2}", report.cases_per_100k_person_weeks.two_doses)
+        }),
+        ("Zak./100 tys. osobotyg. (3)", &|_, report| {
+            format!("{:.2}", report.cases_per_100k_person_weeks.three_doses)
+        }),
         // Deaths
         ("Zg. (NZ)", &|_, report| {
             format!("{}", report.absolute_deaths.unvaccinated)
         }),
+        ("Zg. (1)", &|_, report| {
+            format!("{}", report.absolute_deaths.one_dose)
+        }),
         ("Zg. (2)", &|_, report| {
             format!("{}", report.absolute_deaths.two_doses)
         }),
@@ -62,30 +108,119 @@ pub(crate) fn print_stats_for_age_group(
         ("Zg./mln (NZ)", &|_, report| {
             format!("{:.2}", report.deaths_per_million.unvaccinated)
         }),
+        ("Zg./mln (1)", &|_, report| {
+            format!("{:.2}", report.deaths_per_million.one_dose)
+        }),
         ("Zg./mln (2)", &|_, report| {
             format!("{:.2}", report.deaths_per_million.two_doses)
         }),
         ("Zg./mln (3)", &|_, report| {
             format!("{:.2}", report.deaths_per_million.three_doses)
         }),
+        // Deaths per 100k person-weeks
+        ("Zg./100 tys. osobotyg. (NZ)", &|_, report| {
+            format!("{:.2}", report.deaths_per_100k_person_weeks.unvaccinated)
+        }),
+        ("Zg./100 tys. osobotyg. (1)", &|_, report| {
+            format!("{:.2}", report.deaths_per_100k_person_weeks.one_dose)
+        }),
+        ("Zg./100 tys. osobotyg. (2)", &|_, report| {
+            format!("{:.2}", report.deaths_per_100k_person_weeks.two_doses)
+        }),
+        ("Zg./100 tys. osobotyg. (3)", &|_, report| {
+            format!("{:.2}", report.deaths_per_100k_person_weeks.three_doses)
+        }),
         // RR of case
+        ("RR zak. (1)", &|_, report| {
+            rounded(report.case_risk_ratio_of_one_dose().value, 2)
+        }),
+        ("95% PU zak. (1)", &|_, report| {
+            report.case_risk_ratio_of_one_dose().interval()
+        }),
         ("RR zak. (2)", &|_, report| {
-            format!("{:.2}", report.case_risk_ratio_of_two_doses())
+            rounded(report.case_risk_ratio_of_two_doses().value, 2)
+        }),
+        ("95% PU zak. (2)", &|_, report| {
+            report.case_risk_ratio_of_two_doses().interval()
         }),
         ("RR zak. (3)", &|_, report| {
-            format!("{:.2}", report.case_risk_ratio_of_three_doses())
+            rounded(report.case_risk_ratio_of_three_doses().value, 2)
+        }),
+        ("95% PU zak. (3)", &|_, report| {
+            report.case_risk_ratio_of_three_doses().interval()
         }),
         // RR of death
+        ("RR zg. (1)", &|_, report| {
+            rounded(report.risk_ratio_of_one_dose().value, 2)
+        }),
+        ("95% PU zg. (1)", &|_, report| {
+            report.risk_ratio_of_one_dose().interval()
+        }),
         ("RR zg. (2)", &|_, report| {
-            format!("{:.2}", report.risk_ratio_of_two_doses())
+            rounded(report.risk_ratio_of_two_doses().value, 2)
+        }),
+        ("95% PU zg. (2)", &|_, report| {
+            report.risk_ratio_of_two_doses().interval()
         }),
         ("RR zg. (3)", &|_, report| {
-            format!("{:.2}", report.risk_ratio_of_three_doses())
+            rounded(report.risk_ratio_of_three_doses().value, 2)
+        }),
+        ("95% PU zg. (3)", &|_, report| {
+            report.risk_ratio_of_three_doses().interval()
+        }),
+        // Vaccine effectiveness, in percent
+        ("VE zak. (1) %", &|_, report| {
+            rounded(report.case_effectiveness_of_one_dose().percent().value, 1)
+        }),
+        ("95% PU VE zak. (1)", &|_, report| {
+            report.case_effectiveness_of_one_dose().percent().interval()
+        }),
+        ("VE zak. (2) %", &|_, report| {
+            rounded(report.case_effectiveness_of_two_doses().percent().value, 1)
+        }),
+        ("95% PU VE zak. (2)", &|_, report| {
+            report
+                .case_effectiveness_of_two_doses()
+                .percent()
+                .interval()
+        }),
+        ("VE zak. (3) %", &|_, report| {
+            rounded(
+                report.case_effectiveness_of_three_doses().percent().value,
+                1,
+            )
+        }),
+        ("95% PU VE zak. (3)", &|_, report| {
+            report
+                .case_effectiveness_of_three_doses()
+                .percent()
+                .interval()
+        }),
+        ("VE zg. (1) %", &|_, report| {
+            rounded(report.effectiveness_of_one_dose().percent().value, 1)
+        }),
+        ("95% PU VE zg. (1)", &|_, report| {
+            report.effectiveness_of_one_dose().percent().interval()
+        }),
+        ("VE zg. (2) %", &|_, report| {
+            rounded(report.effectiveness_of_two_doses().percent().value, 1)
+        }),
+        ("95% PU VE zg. (2)", &|_, report| {
+            report.effectiveness_of_two_doses().percent().interval()
+        }),
+        ("VE zg. (3) %", &|_, report| {
+            rounded(report.effectiveness_of_three_doses().percent().value, 1)
+        }),
+        ("95% PU VE zg. (3)", &|_, report| {
+            report.effectiveness_of_three_doses().percent().interval()
         }),
         // CFR
         ("CFR (NZ)", &|_, report| {
             format!("{:.3}", report.cfr_unvaccinated())
         }),
+        ("CFR (1)", &|_, report| {
+            format!("{:.3}", report.cfr_one_dose())
+        }),
         ("CFR (2)", &|_, report| {
             format!("{:.3}", report.cfr_two_doses())
         }),
@@ -100,12 +235,188 @@ pub(crate) fn print_stats_for_age_group(
                 .collect(),
         ));
     }
-    table.print_tty(false);
-
-    let csv = std::fs::File::create(format!(
-        "output/details_for_{}_{}.csv",
-        age_group.0 .0, age_group.0 .1
-    ))
-    .unwrap();
-    table.to_csv(csv).unwrap();
+
+    table
+}
+
+/// Writes a table of an age group as `details_for_<age group>.csv`.
+pub fn write_csv(table: &Table, age_group: AgeGroup, output: &Path) -> anyhow::Result<()> {
+    write(
+        table,
+        &output.join(format!("details_for_{}.csv", age_group.slug())),
+    )
+}
+
+/// Writes a summary table, e.g. of rate ratios by time since the last dose, as
+/// `<name>.csv`.
+pub fn write_summary_csv(table: &Table, name: &str, output: &Path) -> anyhow::Result<()> {
+    write(table, &output.join(format!("{}.csv", name)))
+}
+
+fn write(table: &Table, path: &Path) -> anyhow::Result<()> {
+    let csv =
+        std::fs::File::create(path).with_context(|| format!("can't create {}", path.display()))?;
+    table.to_csv(csv)?;
+    Ok(())
+}
+
+/// Table of rate ratios by time since the last dose, one row per status and band.
+pub fn waning(waning: &[Waning]) -> Table {
+    let mut table = Table::new();
+    table.set_format(*prettytable::format::consts::FORMAT_CLEAN);
+
+    let data: Vec<WaningColumn> = vec![
+        ("Dawki", &|waning| match waning.status {
+            VaccinationStatus::ThreeDoses => "3".to_owned(),
+            _ => "2".to_owned(),
+        }),
+        ("Od ost. dawki", &|waning| waning.band.to_string()),
+        ("Tyg. x grupy", &|waning| format!("{}", waning.cells)),
+        ("Osobotyg.", &|waning| format!("{:.0}", waning.person_weeks)),
+        ("Zak.", &|waning| format!("{:.1}", waning.cases)),
+        ("RR zak.", &|waning| {
+            rounded(waning.case_rate_ratio.value, 2)
+        }),
+        ("95% PU zak.", &|waning| waning.case_rate_ratio.interval()),
+        ("Zg.", &|waning| format!("{:.1}", waning.deaths)),
+        ("RR zg.", &|waning| {
+            rounded(waning.death_rate_ratio.value, 2)
+        }),
+        ("95% PU zg.", &|waning| waning.death_rate_ratio.interval()),
+    ];
+
+    table.add_row(Row::new(data.iter().map(|row| Cell::new(row.0)).collect()));
+    for waning in waning {
+        table.add_row(Row::new(
+            data.iter()
+                .map(|row| Cell::new(row.1(waning).as_str()))
+                .collect(),
+        ));
+    }
+
+    table
+}
+
+/// Table of rate ratios pooled over age groups of several strata, e.g. adjusted for sex,
+/// week by week.
+pub fn pooled_rate_ratios(strata: &[WeeklyReports]) -> Table {
+    let mut table = Table::new();
+    table.set_format(*prettytable::format::consts::FORMAT_CLEAN);
+
+    let data: Vec<PooledColumn> = vec![
+        ("zak. (1)", &WeeklyReport::cases_of_one_dose),
+        ("zak. (2)", &WeeklyReport::cases_of_two_doses),
+        ("zak. (3)", &WeeklyReport::cases_of_three_doses),
+        ("zg. (1)", &WeeklyReport::deaths_of_one_dose),
+        ("zg. (2)", &WeeklyReport::deaths_of_two_doses),
+        ("zg. (3)", &WeeklyReport::deaths_of_three_doses),
+    ];
+    let columns: Vec<Vec<(YearWeek, Estimate)>> = data
+        .iter()
+        .map(|(_, comparison)| WeeklyReports::pooled_over(strata, comparison))
+        .collect();
+
+    let mut header = vec![Cell::new("Tydzień")];
+    for (name, _) in &data {
+        header.push(Cell::new(&format!("RR {}", name)));
+        header.push(Cell::new(&format!("95% PU {}", name)));
+    }
+    table.add_row(Row::new(header));
+
+    let weeks = columns.first().map_or(0, Vec::len);
+    for n in 0..weeks {
+        let mut row = vec![Cell::new(&columns[0][n].0.to_string())];
+        for column in &columns {
+            let estimate = column[n].1;
+            row.push(Cell::new(&rounded(estimate.value, 2)));
+            row.push(Cell::new(&estimate.interval()));
+        }
+        table.add_row(Row::new(row));
+    }
+
+    table
+}
+
+/// Table for strata without population data, where rates can't be calculated.
+pub fn deaths_for_age_group(
+    weekly_reports: impl Iterator<Item = (YearWeek, WeeklyReport)>,
+) -> Table {
+    let mut table = Table::new();
+    table.set_format(*prettytable::format::consts::FORMAT_CLEAN);
+
+    let data: Vec<Column> = vec![
+        ("Tydzień", &|week, _| format!("{}", week)),
+        ("Zg. (NZ)", &|_, report| {
+            format!("{}", report.absolute_deaths.unvaccinated)
+        }),
+        ("Zg. (1)", &|_, report| {
+            format!("{}", report.absolute_deaths.one_dose)
+        }),
+        ("Zg. (2)", &|_, report| {
+            format!("{}", report.absolute_deaths.two_doses)
+        }),
+        ("Zg. (3)", &|_, report| {
+            format!("{}", report.absolute_deaths.three_doses)
+        }),
+        ("Zg. razem", &|_, report| {
+            format!("{}", report.absolute_deaths.total())
+        }),
+    ];
+
+    table.add_row(Row::new(data.iter().map(|row| Cell::new(row.0)).collect()));
+
+    for (week, report) in weekly_reports {
+        table.add_row(Row::new(
+            data.iter()
+                .map(|row| Cell::new(row.1(week, report).as_str()))
+                .collect(),
+        ));
+    }
+
+    table
+}
+
+/// Table of pooled rate ratios with every protection lag, one row per lag.
+pub fn protection_lags(sensitivity: &[LagSensitivity]) -> Table {
+    let mut table = Table::new();
+    table.set_format(*prettytable::format::consts::FORMAT_CLEAN);
+
+    let data: Vec<LagColumn> = vec![
+        ("Opóźnienie (tyg.)", &|lag| format!("{}", lag.lag)),
+        ("RR zak. (2)", &|lag| {
+            rounded(lag.case_risk_ratio_of_two_doses.value, 2)
+        }),
+        ("95% PU zak. (2)", &|lag| {
+            lag.case_risk_ratio_of_two_doses.interval()
+        }),
+        ("RR zak. (3)", &|lag| {
+            rounded(lag.case_risk_ratio_of_three_doses.value, 2)
+        }),
+        ("95% PU zak. (3)", &|lag| {
+            lag.case_risk_ratio_of_three_doses.interval()
+        }),
+        ("RR zg. (2)", &|lag| {
+            rounded(lag.risk_ratio_of_two_doses.value, 2)
+        }),
+        ("95% PU zg. (2)", &|lag| {
+            lag.risk_ratio_of_two_doses.interval()
+        }),
+        ("RR zg. (3)", &|lag| {
+            rounded(lag.risk_ratio_of_three_doses.value, 2)
+        }),
+        ("95% PU zg. (3)", &|lag| {
+            lag.risk_ratio_of_three_doses.interval()
+        }),
+    ];
+
+    table.add_row(Row::new(data.iter().map(|row| Cell::new(row.0)).collect()));
+    for lag in sensitivity {
+        table.add_row(Row::new(
+            data.iter()
+                .map(|row| Cell::new(row.1(lag).as_str()))
+                .collect(),
+        ));
+    }
+
+    table
 }
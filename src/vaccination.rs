@@ -1,42 +1,137 @@
+//! Vaccinations by vaccine and number of doses, from ECDC.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::anyhow;
 use serde::{Deserialize, Deserializer};
 
-use crate::YearWeek;
+use crate::{
+    errors::{self, InvalidValue, Location},
+    quality::{Rejections, Sample},
+    regions::Voivodeship,
+    AgeGroup, Stratum, YearWeek,
+};
+
+/// Vaccine product, as identified by ECDC (`Vaccine`) and BASiW (`producent`).
+#[derive(Debug, Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Product {
+    #[serde(alias = "COM", alias = "Pfizer")]
+    Pfizer,
+    #[serde(alias = "MOD", alias = "Moderna")]
+    Moderna,
+    #[serde(alias = "AZ", alias = "Astra Zeneca")]
+    AstraZeneca,
+    #[serde(alias = "JANSS", alias = "Johnson&Johnson")]
+    Janssen,
+    #[serde(other)]
+    Other,
+}
+
+impl Product {
+    /// Every vaccine used in Poland.
+    pub const KNOWN: &'static [Product] = &[
+        Product::Pfizer,
+        Product::Moderna,
+        Product::AstraZeneca,
+        Product::Janssen,
+    ];
+
+    /// Code of the vaccine in ECDC data, e.g. `COM`.
+    pub fn ecdc_code(&self) -> &'static str {
+        match self {
+            Product::Pfizer => "COM",
+            Product::Moderna => "MOD",
+            Product::AstraZeneca => "AZ",
+            Product::Janssen => "JANSS",
+            Product::Other => "UNK",
+        }
+    }
+
+    /// ASCII name, usable in file names.
+    pub fn slug(&self) -> &'static str {
+        match self {
+            Product::Pfizer => "pfizer",
+            Product::Moderna => "moderna",
+            Product::AstraZeneca => "astrazeneca",
+            Product::Janssen => "janssen",
+            Product::Other => "inne",
+        }
+    }
+}
+
+impl Display for Product {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Product::Pfizer => write!(f, "Pfizer"),
+            Product::Moderna => write!(f, "Moderna"),
+            Product::AstraZeneca => write!(f, "AstraZeneca"),
+            Product::Janssen => write!(f, "Johnson&Johnson"),
+            Product::Other => write!(f, "inne szczepionki"),
+        }
+    }
+}
+
+impl FromStr for Product {
+    type Err = anyhow::Error;
+
+    /// Accepts ECDC codes (`COM`) as well as names (`pfizer`).
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Product::KNOWN
+            .iter()
+            .find(|product| {
+                text.eq_ignore_ascii_case(product.ecdc_code())
+                    || text.eq_ignore_ascii_case(product.slug())
+            })
+            .copied()
+            .ok_or_else(|| anyhow!("unknown vaccine: '{}', expected e.g. COM or pfizer", text))
+    }
+}
 
 fn deserialize_year_week<'a, D>(deserializer: D) -> Result<YearWeek, D::Error>
 where
     D: Deserializer<'a>,
 {
     let text: &str = Deserialize::deserialize(deserializer)?;
-    let re = regex::Regex::new(r"(\d{4})-W(\d{2})").unwrap();
-    let captures = re.captures(text).unwrap();
-    Ok(YearWeek((
-        captures.get(1).unwrap().as_str().parse().unwrap(),
-        captures.get(2).unwrap().as_str().parse().unwrap(),
-    )))
+    text.parse().map_err(|_| InvalidValue::Week.error())
 }
 
-fn deserialize_age_group<'a, D>(deserializer: D) -> Result<(usize, usize), D::Error>
+fn deserialize_age_group<'a, D>(deserializer: D) -> Result<AgeGroup, D::Error>
 where
     D: Deserializer<'a>,
 {
     let text: &str = Deserialize::deserialize(deserializer)?;
 
     match text {
-        "Age0_4" => Ok((0, 4)),
-        "Age5_9" => Ok((5, 9)),
-        "Age10_14" => Ok((10, 14)),
-        "Age15_17" => Ok((15, 17)),
-        "Age18_24" => Ok((18, 24)),
-        "Age25_49" => Ok((25, 49)),
-        "Age50_59" => Ok((50, 59)),
-        "Age60_69" => Ok((60, 69)),
-        "Age70_79" => Ok((70, 79)),
-        "Age80+" => Ok((80, 120)),
-        "ALL" | "AgeUNK" | "HCW" => Err(serde::de::Error::custom(format!(
-            "don't care about this one '{}'",
-            text
-        ))),
-        _ => panic!("{}", text),
+        "Age0_4" => Ok(AgeGroup::new(0, 4)),
+        "Age5_9" => Ok(AgeGroup::new(5, 9)),
+        "Age10_14" => Ok(AgeGroup::new(10, 14)),
+        "Age15_17" => Ok(AgeGroup::new(15, 17)),
+        "Age18_24" => Ok(AgeGroup::new(18, 24)),
+        "Age25_49" => Ok(AgeGroup::new(25, 49)),
+        "Age50_59" => Ok(AgeGroup::new(50, 59)),
+        "Age60_69" => Ok(AgeGroup::new(60, 69)),
+        "Age70_79" => Ok(AgeGroup::new(70, 79)),
+        "Age80+" => Ok(AgeGroup::open_ended(80)),
+        _ => Err(InvalidValue::TargetGroup.error()),
+    }
+}
+
+/// `PL` stands for the whole country, NUTS codes for voivodeships.
+fn deserialize_region<'a, D>(deserializer: D) -> Result<Option<Voivodeship>, D::Error>
+where
+    D: Deserializer<'a>,
+{
+    let text: &str = Deserialize::deserialize(deserializer)?;
+    match text {
+        "PL" => Ok(None),
+        _ => Voivodeship::from_nuts(text)
+            .map(Some)
+            .ok_or_else(|| InvalidValue::Region.error()),
     }
 }
 
@@ -44,12 +139,12 @@ where
 struct VaccinationEcdcRow {
     #[serde(alias = "YearWeekISO", deserialize_with = "deserialize_year_week")]
     year_week: YearWeek,
-    #[serde(alias = "ReportingCountry")]
-    country: String,
-    #[serde(alias = "Region")]
-    region: String,
+    #[serde(alias = "Region", deserialize_with = "deserialize_region")]
+    region: Option<Voivodeship>,
+    #[serde(alias = "Denominator")]
+    population: Option<usize>,
     #[serde(alias = "TargetGroup", deserialize_with = "deserialize_age_group")]
-    age_group: (usize, usize),
+    age_group: AgeGroup,
     #[serde(alias = "FirstDose")]
     first_dose: usize,
     #[serde(alias = "SecondDose")]
@@ -57,74 +152,471 @@ struct VaccinationEcdcRow {
     #[serde(alias = "DoseAdditional1")]
     third_dose: usize,
     #[serde(alias = "Vaccine")]
-    vaccine: String,
+    vaccine: Product,
 }
 
-fn read_vaccinations() -> Vec<VaccinationEcdcRow> {
-    let file = std::fs::File::open("data/vaccines-pl.csv").unwrap();
-    csv::ReaderBuilder::new()
-        .from_reader(file)
-        .deserialize::<VaccinationEcdcRow>()
-        .filter_map(Result::ok)
-        .filter(|r| r.country == "PL" && r.region == "PL")
-        .collect()
+/// Target groups reported besides age groups, which overlap them.
+const IGNORED_TARGET_GROUPS: &[&str] = &["ALL", "HCW"];
+
+/// Target group of people of unknown age, who are left out of every age group.
+const UNKNOWN_AGE: &str = "AgeUNK";
+
+/// Error of the ECDC file.
+#[derive(Debug, thiserror::Error)]
+pub enum EcdcError {
+    #[error("can't open {location}")]
+    Open {
+        location: Location,
+        source: std::io::Error,
+    },
+    #[error("{location}: malformed CSV file")]
+    Csv {
+        location: Location,
+        source: csv::Error,
+    },
+    #[error("{location}: missing column {column}")]
+    MissingColumn { location: Location, column: String },
+}
+
+/// Reads Polish rows of the ECDC file. Rows which can't be read, and rows of people of
+/// unknown age, are accounted for as dropped, with the people who got their first dose.
+/// Those are counted from national rows only, as regional ones report the same people again.
+fn read_vaccinations(path: &Path) -> Result<(Vec<VaccinationEcdcRow>, Rejections), EcdcError> {
+    let csv_error = |source: csv::Error| EcdcError::Csv {
+        location: Location::csv(path, &source),
+        source,
+    };
+
+    let file = std::fs::File::open(path).map_err(|source| EcdcError::Open {
+        location: Location::file(path),
+        source,
+    })?;
+    let mut reader = csv::Reader::from_reader(file);
+    let headers = reader.headers().map_err(csv_error)?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header == name)
+            .ok_or_else(|| EcdcError::MissingColumn {
+                location: Location::file(path),
+                column: name.to_owned(),
+            })
+    };
+    let country = column("ReportingCountry")?;
+    let region = column("Region")?;
+    let target_group = column("TargetGroup")?;
+    let first_dose = column("FirstDose")?;
+
+    let mut rows = Vec::new();
+    let mut rejections = Rejections::new(path);
+    for record in reader.records() {
+        let record = record.map_err(csv_error)?;
+        if &record[country] != "PL" || IGNORED_TARGET_GROUPS.contains(&&record[target_group]) {
+            continue;
+        }
+        let people = match &record[region] {
+            "PL" => record[first_dose].parse().unwrap_or_default(),
+            _ => 0,
+        };
+        let sample = |message: String| Sample {
+            line: record.position().map(|position| position.line()),
+            message,
+            row: record.iter().map(str::to_owned).collect(),
+        };
+        if &record[target_group] == UNKNOWN_AGE {
+            let message = format!("target group {}", UNKNOWN_AGE);
+            rejections.reject("unknown age".to_owned(), people, sample(message));
+            continue;
+        }
+        match record.deserialize(Some(&headers)) {
+            Ok(row) => {
+                rejections.accept(people);
+                rows.push(row);
+            }
+            Err(error) => {
+                let reason = match errors::reason(&error, &headers) {
+                    Some(reason) => reason,
+                    None => return Err(csv_error(error)),
+                };
+                rejections.reject(reason, people, sample(errors::message(&error)));
+            }
+        }
+    }
+    Ok((rows, rejections))
 }
 
+/// People by the number of doses they got, cumulatively as of a given week. A single dose
+/// of Johnson&Johnson counts as two.
 #[derive(Debug, Clone, Copy, Default)]
-pub(crate) struct VaccinatedPeople {
+pub struct VaccinatedPeople {
+    /// People who got the first dose.
     pub at_least_one_dose: usize,
+    /// People fully vaccinated, booster or not.
     pub at_least_two_doses: usize,
+    /// People who got a booster.
     pub at_least_three_doses: usize,
+    /// People with the first dose only.
     pub one_dose: usize,
+    /// People fully vaccinated, without a booster.
     pub two_doses: usize,
+    /// People who got a booster.
     pub three_doses: usize,
 }
 
 impl VaccinatedPeople {
+    /// Takes a given share of every group, e.g. to estimate how many of them are women.
+    pub fn scale(self, share: f64) -> Self {
+        let scale = |count: usize| (count as f64 * share).round() as usize;
+        Self {
+            at_least_one_dose: scale(self.at_least_one_dose),
+            at_least_two_doses: scale(self.at_least_two_doses),
+            at_least_three_doses: scale(self.at_least_three_doses),
+            one_dose: scale(self.one_dose),
+            two_doses: scale(self.two_doses),
+            three_doses: scale(self.three_doses),
+        }
+    }
+
     fn update(self, rhs: &VaccinationEcdcRow) -> Self {
+        // Janssen is a single dose vaccine and BASiW considers people who got it fully
+        // vaccinated, same as after two doses of any other one. Its second shot is
+        // reported as a booster.
+        let full_course = if rhs.vaccine == Product::Janssen {
+            rhs.first_dose
+        } else {
+            rhs.second_dose
+        };
+
         Self {
             at_least_one_dose: self.at_least_one_dose + rhs.first_dose,
-            at_least_two_doses: self.at_least_two_doses + rhs.second_dose,
+            at_least_two_doses: self.at_least_two_doses + full_course,
             at_least_three_doses: self.at_least_three_doses + rhs.third_dose,
-            // Doing checked subtraction since there are some discrepancies in the ECDC data,
+            // Doing saturating subtraction since there are some discrepancies in the ECDC data,
             // showing couple people as vaccinated with booster before getting second dose.
-            // For JJ 1-dose vaccine, second is shot is considered a booster. As such, we
-            // have to mind from which set to decrement.
-            one_dose: (self.one_dose + rhs.first_dose)
-                .checked_sub(if rhs.vaccine == "JANSS" {
-                    rhs.third_dose
-                } else {
-                    rhs.second_dose
-                })
-                .unwrap_or_default(),
-            two_doses: (self.two_doses + rhs.second_dose)
-                .checked_sub(if rhs.vaccine == "JANSS" {
-                    0
-                } else {
-                    rhs.third_dose
-                })
-                .unwrap_or_default(),
+            one_dose: (self.one_dose + rhs.first_dose).saturating_sub(full_course),
+            two_doses: (self.two_doses + full_course).saturating_sub(rhs.third_dose),
             three_doses: self.three_doses + rhs.third_dose,
         }
     }
 }
 
-#[derive(Default)]
-pub(crate) struct VaccinationData {
+/// Time people spent with a given number of doses during a single week, in person-weeks.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PersonWeeks {
+    /// Of people who got the first dose.
+    pub at_least_one_dose: f64,
+    /// Of people with the first dose only.
+    pub one_dose: f64,
+    /// Of people fully vaccinated, without a booster.
+    pub two_doses: f64,
+    /// Of people who got a booster.
+    pub three_doses: f64,
+}
+
+impl PersonWeeks {
+    /// Person-weeks of a week which starts with `start` people and ends with `end` ones.
+    /// Doses are assumed to be given evenly over the week, so everyone vaccinated during
+    /// it spends half of it with the new dose.
+    pub fn between(start: VaccinatedPeople, end: VaccinatedPeople) -> Self {
+        let mean = |start: usize, end: usize| (start + end) as f64 / 2.0;
+        Self {
+            at_least_one_dose: mean(start.at_least_one_dose, end.at_least_one_dose),
+            one_dose: mean(start.one_dose, end.one_dose),
+            two_doses: mean(start.two_doses, end.two_doses),
+            three_doses: mean(start.three_doses, end.three_doses),
+        }
+    }
+
+    /// Takes a given share of every group, e.g. to estimate how many of them are women.
+    pub fn scale(self, share: f64) -> Self {
+        Self {
+            at_least_one_dose: self.at_least_one_dose * share,
+            one_dose: self.one_dose * share,
+            two_doses: self.two_doses * share,
+            three_doses: self.three_doses * share,
+        }
+    }
+}
+
+/// Fully vaccinated and boosted people by the week they got the dose which gave them their
+/// status, oldest first. Weeks nobody is left from are omitted.
+#[derive(Debug, Clone, Default)]
+pub struct Cohorts {
+    /// People fully vaccinated, without a booster, by the week of their full course.
+    pub two_doses: Vec<(YearWeek, usize)>,
+    /// People who got a booster, by the week of it.
+    pub three_doses: Vec<(YearWeek, usize)>,
+}
+
+impl Cohorts {
+    /// Adds doses of a week, taking boosters from the oldest full courses.
+    fn add(&mut self, week: YearWeek, full_courses: usize, boosters: usize) {
+        if full_courses > 0 {
+            self.two_doses.push((week, full_courses));
+        }
+        let mut left = boosters;
+        for (_, cohort) in &mut self.two_doses {
+            let boosted = left.min(*cohort);
+            *cohort -= boosted;
+            left -= boosted;
+            if left == 0 {
+                break;
+            }
+        }
+        self.two_doses.retain(|(_, people)| *people > 0);
+        if boosters > 0 {
+            self.three_doses.push((week, boosters));
+        }
+    }
+}
+
+/// Region (`None` for the whole country), age group and vaccine (`None` for any of them).
+type CumulativeKey = (Option<Voivodeship>, AgeGroup, Option<Product>);
+
+/// Vaccinations reported by ECDC, by week, region, age group and vaccine.
+pub struct VaccinationData {
+    /// Rows dropped while reading.
+    pub rejections: Rejections,
     rows: Vec<VaccinationEcdcRow>,
+    /// Vaccinated people at the end of every reported week, in order of weeks.
+    cumulative: HashMap<CumulativeKey, Vec<(YearWeek, VaccinatedPeople)>>,
 }
 
 impl VaccinationData {
-    pub fn sum(&self, age_group: (usize, usize), week: YearWeek) -> VaccinatedPeople {
-        self.rows
+    /// Vaccinated people of stratum's voivodeship (or the whole country) who got stratum's
+    /// vaccine (or any of them).
+    pub fn sum(&self, age_group: AgeGroup, week: YearWeek, stratum: &Stratum) -> VaccinatedPeople {
+        let Some(weeks) = self
+            .cumulative
+            .get(&(stratum.region, age_group, stratum.product))
+        else {
+            return VaccinatedPeople::default();
+        };
+        match weeks.partition_point(|(reported, _)| *reported <= week) {
+            0 => VaccinatedPeople::default(),
+            count => weeks[count - 1].1,
+        }
+    }
+
+    /// Person-weeks of stratum's voivodeship (or the whole country) and vaccine (or any of
+    /// them) during a week, interpolated between people vaccinated at its start and end.
+    pub fn person_weeks(
+        &self,
+        age_group: AgeGroup,
+        week: YearWeek,
+        stratum: &Stratum,
+    ) -> PersonWeeks {
+        let start = week
+            .weeks_before(1)
+            .map(|previous| self.sum(age_group, previous, stratum))
+            .unwrap_or_default();
+        PersonWeeks::between(start, self.sum(age_group, week, stratum))
+    }
+
+    /// People of stratum's voivodeship and vaccine by the week they got their last dose, as
+    /// of a given week. ECDC reports doses rather than people, so boosters are assumed to
+    /// go to those who completed their full course the longest ago.
+    pub fn cohorts(&self, age_group: AgeGroup, week: YearWeek, stratum: &Stratum) -> Cohorts {
+        let mut cohorts = Cohorts::default();
+        for (reported, full_courses, boosters) in self.doses(age_group, stratum) {
+            if reported > week {
+                break;
+            }
+            cohorts.add(reported, full_courses, boosters);
+        }
+        cohorts
+    }
+
+    /// Cohorts as of every reported week, in order of weeks, built in a single pass.
+    pub fn cohorts_by_week(
+        &self,
+        age_group: AgeGroup,
+        stratum: &Stratum,
+    ) -> Vec<(YearWeek, Cohorts)> {
+        let mut cohorts = Cohorts::default();
+        self.doses(age_group, stratum)
+            .map(|(reported, full_courses, boosters)| {
+                cohorts.add(reported, full_courses, boosters);
+                (reported, cohorts.clone())
+            })
+            .collect()
+    }
+
+    /// Full courses and boosters given during every reported week.
+    fn doses(
+        &self,
+        age_group: AgeGroup,
+        stratum: &Stratum,
+    ) -> impl Iterator<Item = (YearWeek, usize, usize)> + '_ {
+        let weeks = self
+            .cumulative
+            .get(&(stratum.region, age_group, stratum.product))
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let previous = std::iter::once(VaccinatedPeople::default())
+            .chain(weeks.iter().map(|(_, people)| *people));
+        weeks
             .iter()
-            .filter(|row| row.year_week <= week && row.age_group == age_group)
-            .fold(VaccinatedPeople::default(), VaccinatedPeople::update)
+            .zip(previous)
+            .map(|((reported, people), previous)| {
+                (
+                    *reported,
+                    people
+                        .at_least_two_doses
+                        .saturating_sub(previous.at_least_two_doses),
+                    people
+                        .at_least_three_doses
+                        .saturating_sub(previous.at_least_three_doses),
+                )
+            })
     }
 
-    pub fn new() -> Self {
-        Self {
-            rows: read_vaccinations(),
+    /// Share of each age group's population living in a given voivodeship, according to
+    /// the denominators ECDC reports along with the vaccinations. Some regions (PL92X)
+    /// come without a denominator, they get whatever is left from the rest of the country.
+    pub fn regional_shares(&self) -> HashMap<(Voivodeship, AgeGroup), f64> {
+        let mut populations = HashMap::<(Option<Voivodeship>, AgeGroup), usize>::new();
+        for row in &self.rows {
+            if let Some(population) = row.population {
+                populations.insert((row.region, row.age_group), population);
+            }
+        }
+
+        let mut shares: HashMap<_, _> = populations
+            .iter()
+            .filter_map(|((region, age_group), population)| {
+                let region = (*region)?;
+                let country = populations.get(&(None, *age_group))?;
+                Some(((region, *age_group), *population as f64 / *country as f64))
+            })
+            .collect();
+
+        let age_groups: HashSet<AgeGroup> = self.rows.iter().map(|row| row.age_group).collect();
+        for age_group in age_groups {
+            let missing: Vec<Voivodeship> = Voivodeship::all()
+                .filter(|region| !shares.contains_key(&(*region, age_group)))
+                .collect();
+            let known: f64 = Voivodeship::all()
+                .filter_map(|region| shares.get(&(region, age_group)))
+                .sum();
+            for region in &missing {
+                shares.insert(
+                    (*region, age_group),
+                    (1.0 - known).max(0.0) / missing.len() as f64,
+                );
+            }
         }
+
+        shares
+    }
+
+    /// Reads Polish rows of the ECDC file.
+    pub fn new(path: &Path) -> Result<Self, EcdcError> {
+        let (mut rows, rejections) = read_vaccinations(path)?;
+        rows.sort_by_key(|row| row.year_week);
+
+        let mut cumulative = HashMap::<CumulativeKey, Vec<(YearWeek, VaccinatedPeople)>>::new();
+        for row in &rows {
+            for product in [None, Some(row.vaccine)] {
+                let weeks = cumulative
+                    .entry((row.region, row.age_group, product))
+                    .or_default();
+                match weeks.last_mut() {
+                    Some((week, people)) if *week == row.year_week => *people = people.update(row),
+                    last => {
+                        let people = last.map(|(_, people)| *people).unwrap_or_default();
+                        weeks.push((row.year_week, people.update(row)));
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            rejections,
+            rows,
+            cumulative,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(
+        vaccine: Product,
+        first_dose: usize,
+        second_dose: usize,
+        third_dose: usize,
+    ) -> VaccinationEcdcRow {
+        VaccinationEcdcRow {
+            year_week: YearWeek((2021, 52)),
+            region: None,
+            population: None,
+            age_group: AgeGroup::open_ended(80),
+            first_dose,
+            second_dose,
+            third_dose,
+            vaccine,
+        }
+    }
+
+    #[test]
+    fn two_doses_make_a_full_course() {
+        let people = VaccinatedPeople::default()
+            .update(&row(Product::Pfizer, 100, 0, 0))
+            .update(&row(Product::Pfizer, 0, 80, 30));
+
+        assert_eq!(people.at_least_one_dose, 100);
+        assert_eq!(people.at_least_two_doses, 80);
+        assert_eq!(people.at_least_three_doses, 30);
+        assert_eq!(people.one_dose, 20);
+        assert_eq!(people.two_doses, 50);
+        assert_eq!(people.three_doses, 30);
+    }
+
+    #[test]
+    fn single_dose_of_janssen_is_a_full_course() {
+        let people = VaccinatedPeople::default().update(&row(Product::Janssen, 100, 0, 0));
+
+        assert_eq!(people.at_least_one_dose, 100);
+        assert_eq!(people.at_least_two_doses, 100);
+        assert_eq!(people.one_dose, 0);
+        assert_eq!(people.two_doses, 100);
+        assert_eq!(people.three_doses, 0);
+    }
+
+    #[test]
+    fn second_dose_of_janssen_is_a_booster() {
+        let people = VaccinatedPeople::default()
+            .update(&row(Product::Janssen, 100, 0, 0))
+            .update(&row(Product::Janssen, 0, 0, 40));
+
+        assert_eq!(people.at_least_two_doses, 100);
+        assert_eq!(people.at_least_three_doses, 40);
+        assert_eq!(people.one_dose, 0);
+        assert_eq!(people.two_doses, 60);
+        assert_eq!(people.three_doses, 40);
+    }
+
+    #[test]
+    fn boosters_reported_ahead_of_second_doses_do_not_underflow() {
+        let people = VaccinatedPeople::default().update(&row(Product::Moderna, 10, 5, 20));
+
+        assert_eq!(people.one_dose, 5);
+        assert_eq!(people.two_doses, 0);
+        assert_eq!(people.three_doses, 20);
+    }
+
+    #[test]
+    fn people_vaccinated_within_a_week_count_half_of_it() {
+        let start = VaccinatedPeople::default().update(&row(Product::Pfizer, 100, 0, 0));
+        let end = start.update(&row(Product::Pfizer, 0, 80, 30));
+        let person_weeks = PersonWeeks::between(start, end);
+
+        assert_eq!(person_weeks.at_least_one_dose, 100.0);
+        assert_eq!(person_weeks.one_dose, 60.0);
+        assert_eq!(person_weeks.two_doses, 25.0);
+        assert_eq!(person_weeks.three_doses, 15.0);
     }
 }
@@ -0,0 +1,214 @@
+//! Rate ratios by time since the last dose, to see whether protection wanes.
+
+use std::fmt::Display;
+
+use crate::{
+    datasets::Datasets,
+    deaths::VaccinationStatus,
+    intervals::{mantel_haenszel_of_shares, Comparison, Estimate},
+    vaccination::Cohorts,
+    WeeklyReport, WeeklyReports, YearWeek,
+};
+
+/// Range of whole weeks since the last dose, both ends included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SinceDose {
+    from: usize,
+    /// `None` for the last band.
+    to: Option<usize>,
+}
+
+impl SinceDose {
+    /// Weeks from `from` to `to`.
+    pub const fn new(from: usize, to: usize) -> Self {
+        Self { from, to: Some(to) }
+    }
+
+    /// Weeks from `from` on.
+    pub const fn open_ended(from: usize) -> Self {
+        Self { from, to: None }
+    }
+
+    /// Whether the number of weeks belongs to this band.
+    pub fn includes(&self, weeks: usize) -> bool {
+        weeks >= self.from && self.to.is_none_or(|to| weeks <= to)
+    }
+}
+
+impl Display for SinceDose {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.to {
+            Some(to) => write!(f, "{}-{} tyg.", self.from, to),
+            None => write!(f, "{}+ tyg.", self.from),
+        }
+    }
+}
+
+/// Bands of about three months each.
+pub const SINCE_DOSE_BANDS: &[SinceDose] = &[
+    SinceDose::new(0, 12),
+    SinceDose::new(13, 25),
+    SinceDose::new(26, 38),
+    SinceDose::open_ended(39),
+];
+
+/// Rate ratios of people with a vaccination status whose last dose was given some weeks ago,
+/// pooled over weeks and age groups.
+#[derive(Debug, Clone, Copy)]
+pub struct Waning {
+    /// `TwoDoses` or `ThreeDoses`.
+    pub status: VaccinationStatus,
+    /// Weeks since the last dose.
+    pub band: SinceDose,
+    /// Weeks and age groups with anybody in the band.
+    pub cells: usize,
+    /// Person-weeks of people with the status in the band, summed over those weeks and age
+    /// groups.
+    pub person_weeks: f64,
+    /// Deaths of people with the status, attributed to the band by its person-weeks.
+    pub deaths: f64,
+    /// Infections of people with the status, attributed to the band by its person-weeks.
+    pub cases: f64,
+    /// Risk of death relative to the unvaccinated.
+    pub death_rate_ratio: Estimate,
+    /// Risk of infection relative to the unvaccinated.
+    pub case_rate_ratio: Estimate,
+}
+
+/// Shares of cohorts' people in every band of `SINCE_DOSE_BANDS` as of `week`, or `None`
+/// if there's nobody.
+fn band_shares(cohorts: &[(YearWeek, usize)], week: YearWeek) -> Option<Vec<f64>> {
+    let mut people = vec![0; SINCE_DOSE_BANDS.len()];
+    for (given, cohort) in cohorts {
+        let Some(weeks) = week.weeks_since(*given) else {
+            continue;
+        };
+        if let Some(band) = SINCE_DOSE_BANDS
+            .iter()
+            .position(|band| band.includes(weeks))
+        {
+            people[band] += cohort;
+        }
+    }
+    let total: usize = people.iter().sum();
+    if total == 0 {
+        return None;
+    }
+    Some(
+        people
+            .into_iter()
+            .map(|people| people as f64 / total as f64)
+            .collect(),
+    )
+}
+
+/// Status along with its cohorts and comparisons of deaths and infections.
+type Status = (
+    VaccinationStatus,
+    fn(&Cohorts) -> &[(YearWeek, usize)],
+    fn(&WeeklyReport) -> Comparison,
+    fn(&WeeklyReport) -> Comparison,
+);
+
+const STATUSES: &[Status] = &[
+    (
+        VaccinationStatus::TwoDoses,
+        |cohorts| &cohorts.two_doses,
+        WeeklyReport::deaths_of_two_doses,
+        WeeklyReport::cases_of_two_doses,
+    ),
+    (
+        VaccinationStatus::ThreeDoses,
+        |cohorts| &cohorts.three_doses,
+        WeeklyReport::deaths_of_three_doses,
+        WeeklyReport::cases_of_three_doses,
+    ),
+];
+
+/// Rate ratios of fully vaccinated and boosted people by time since their last dose.
+///
+/// BASiW doesn't tell when the dead got their last dose, so deaths can't be split by it.
+/// Instead person-weeks of every week and age group are split across bands by the sizes of
+/// the cohorts in them, counted with the protection lag of the datasets, and deaths and
+/// infections are attributed to the bands in proportion. Rate ratios are pooled over weeks
+/// and age groups of each band. Bands nobody fell into are left out.
+pub fn by_time_since_dose(datasets: &Datasets, reports: &WeeklyReports) -> Vec<Waning> {
+    let stratum = reports.stratum();
+    let mut bands: Vec<Vec<Vec<(&WeeklyReport, f64)>>> =
+        vec![vec![Vec::new(); SINCE_DOSE_BANDS.len()]; STATUSES.len()];
+    for age_group in reports.age_groups() {
+        let cohorts = datasets.vaccinations.cohorts_by_week(*age_group, &stratum);
+        for (week, by_age_group) in reports.weeks() {
+            let protected = week.weeks_before(datasets.protection_lag).unwrap_or(*week);
+            let Some(index) = cohorts
+                .partition_point(|(reported, _)| *reported <= protected)
+                .checked_sub(1)
+            else {
+                continue;
+            };
+            for ((_, cohorts_of, _, _), bands) in STATUSES.iter().zip(&mut bands) {
+                let Some(shares) = band_shares(cohorts_of(&cohorts[index].1), *week) else {
+                    continue;
+                };
+                for (band, share) in bands.iter_mut().zip(shares) {
+                    if share > 0.0 {
+                        band.push((&by_age_group[age_group], share));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut waning = Vec::new();
+    for ((status, _, deaths_of, cases_of), bands) in STATUSES.iter().zip(bands) {
+        for (band, reports) in SINCE_DOSE_BANDS.iter().zip(bands) {
+            if reports.is_empty() {
+                continue;
+            }
+            let deaths: Vec<(Comparison, f64)> = reports
+                .iter()
+                .map(|(report, share)| (deaths_of(report), *share))
+                .collect();
+            let cases: Vec<(Comparison, f64)> = reports
+                .iter()
+                .map(|(report, share)| (cases_of(report), *share))
+                .collect();
+            waning.push(Waning {
+                status: *status,
+                band: *band,
+                cells: reports.len(),
+                person_weeks: deaths
+                    .iter()
+                    .map(|(comparison, share)| comparison.population * share)
+                    .sum(),
+                deaths: deaths
+                    .iter()
+                    .map(|(comparison, share)| comparison.events as f64 * share)
+                    .sum(),
+                cases: cases
+                    .iter()
+                    .map(|(comparison, share)| comparison.events as f64 * share)
+                    .sum(),
+                death_rate_ratio: mantel_haenszel_of_shares(deaths.into_iter()),
+                case_rate_ratio: mantel_haenszel_of_shares(cases.into_iter()),
+            });
+        }
+    }
+    waning
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn people_are_split_across_bands_by_their_cohorts() {
+        let week = YearWeek((2022, 1));
+        let cohorts = [(YearWeek((2021, 32)), 300), (YearWeek((2021, 52)), 100)];
+        assert_eq!(
+            band_shares(&cohorts, week),
+            Some(vec![0.25, 0.75, 0.0, 0.0])
+        );
+        assert_eq!(band_shares(&[], week), None);
+    }
+}
@@ -0,0 +1,15 @@
+//! Paths of the fixture datasets in `tests/fixtures`.
+
+use std::path::PathBuf;
+
+pub fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+}
+
+pub const DEATHS: &str = "ewp_dsh_zgony_po_szczep_202201100940.csv";
+pub const CASES: &str = "ewp_dsh_zakazenia_po_szczepieniu_202201100940.csv";
+pub const VACCINATIONS: &str = "vaccinations.csv";
+pub const POPULATION: &str = "population.xls";
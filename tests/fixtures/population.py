@@ -0,0 +1,125 @@
+#!/usr/bin/env python3
+"""Writes population.xls, a tiny stand-in for the GUS table.
+
+Every single year of age from 0 to 84 has 1000 people (480 males, 520 females),
+"85 lat i więcej" has 5000 (2000 males, 3000 females). A summary row below it
+must be skipped by the loader.
+
+The workbook is written by hand as BIFF8 in a compound file, since there is no
+XLS writer around. Only records the loader needs are written.
+"""
+
+import struct
+from pathlib import Path
+
+SECTOR = 512
+END_OF_CHAIN = 0xFFFFFFFE
+FAT_SECTOR = 0xFFFFFFFD
+FREE = 0xFFFFFFFF
+NO_STREAM = 0xFFFFFFFF
+
+
+def record(kind, data):
+    return struct.pack("<HH", kind, len(data)) + data
+
+
+def bof(kind):
+    return record(0x0809, struct.pack("<HHHHII", 0x0600, kind, 0, 0, 0, 0))
+
+
+def eof():
+    return record(0x000A, b"")
+
+
+def utf16(text):
+    return text.encode("utf-16-le")
+
+
+def rows():
+    yield ["Tablica 1. Ludność według płci i wieku"]
+    yield ["Wiek", "Ogółem", "Mężczyźni", "Kobiety"]
+    for age in range(85):
+        yield [float(age), 1000.0, 480.0, 520.0]
+    yield ["85 lat i więcej", 5000.0, 2000.0, 3000.0]
+    yield ["65 lat i więcej", 25000.0, 11600.0, 13400.0]
+
+
+def sheet(strings):
+    records = []
+    table = list(rows())
+    columns = max(len(row) for row in table)
+    records.append(bof(0x0010))
+    records.append(record(0x0200, struct.pack("<IIHHH", 0, len(table), 0, columns, 0)))
+    for index, row in enumerate(table):
+        for column, value in enumerate(row):
+            if isinstance(value, float):
+                records.append(record(0x0203, struct.pack("<HHHd", index, column, 0, value)))
+            else:
+                if value not in strings:
+                    strings.append(value)
+                position = strings.index(value)
+                records.append(record(0x00FD, struct.pack("<HHHI", index, column, 0, position)))
+    records.append(eof())
+    return b"".join(records)
+
+
+def workbook():
+    strings = []
+    body = sheet(strings)
+
+    sst = struct.pack("<II", len(strings), len(strings))
+    for text in strings:
+        sst += struct.pack("<HB", len(text), 1) + utf16(text)
+
+    name = "Tabl. 1"
+
+    def globals_(position):
+        bound_sheet = struct.pack("<IHBB", position, 0, len(name), 1) + utf16(name)
+        return b"".join(
+            [
+                bof(0x0005),
+                record(0x0042, struct.pack("<H", 1200)),
+                record(0x0085, bound_sheet),
+                record(0x00FC, sst),
+                eof(),
+            ]
+        )
+
+    head = globals_(0)
+    stream = globals_(len(head)) + body
+    # Streams shorter than 4096 bytes would have to go to the mini stream.
+    return stream + b"\0" * (max(4096, -(-len(stream) // SECTOR) * SECTOR) - len(stream))
+
+
+def directory_entry(name, kind, child, start, size):
+    encoded = utf16(name + "\0") if name else b""
+    entry = encoded.ljust(64, b"\0")
+    entry += struct.pack("<HBB", len(encoded), kind, 1)
+    entry += struct.pack("<III", NO_STREAM, NO_STREAM, child)
+    entry += b"\0" * 36
+    entry += struct.pack("<II", start, size)
+    return entry.ljust(128, b"\0")
+
+
+def compound_file(stream):
+    sectors = len(stream) // SECTOR
+    # Sector 0 holds the FAT, sector 1 the directory, the stream follows.
+    fat = [FAT_SECTOR, END_OF_CHAIN] + [2 + i + 1 for i in range(sectors - 1)] + [END_OF_CHAIN]
+    fat += [FREE] * (SECTOR // 4 - len(fat))
+
+    header = b"\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1" + b"\0" * 16
+    header += struct.pack("<HHHHH", 0x003E, 3, 0xFFFE, 9, 6)
+    header += b"\0" * 6
+    header += struct.pack("<IIIIIIIII", 0, 1, 1, 0, 4096, END_OF_CHAIN, 0, END_OF_CHAIN, 0)
+    header += struct.pack("<I", 0) + struct.pack("<I", FREE) * 108
+
+    directory = directory_entry("Root Entry", 5, 1, END_OF_CHAIN, 0)
+    directory += directory_entry("Workbook", 2, NO_STREAM, 2, len(stream))
+    directory += directory_entry("", 0, NO_STREAM, 0, 0) * 2
+
+    return header + struct.pack("<128I", *fat) + directory + stream
+
+
+if __name__ == "__main__":
+    path = Path(__file__).with_name("population.xls")
+    path.write_bytes(compound_file(workbook()))
@@ -0,0 +1,226 @@
+//! Loaders run against the fixture datasets.
+
+mod common;
+
+use common::{fixture, CASES, DEATHS, POPULATION, VACCINATIONS};
+use covid_vaccine_efficacy::{
+    deaths::{DeathsData, InfectionsData, VaccinationStatus},
+    demographics::{age_distribution, Sex},
+    regions::Voivodeship,
+    vaccination::{Product, VaccinationData},
+    AgeGroup, Stratum, YearWeek,
+};
+
+const W52: YearWeek = YearWeek((2021, 52));
+const W01: YearWeek = YearWeek((2022, 1));
+const OLDEST: AgeGroup = AgeGroup::open_ended(80);
+const SIXTIES: AgeGroup = AgeGroup::new(60, 69);
+
+#[test]
+fn deaths_are_read_with_every_spelling_of_their_status() {
+    let deaths = DeathsData::new(&fixture(DEATHS)).unwrap();
+    let count = |week, age_group, status| {
+        deaths.by_vaccination_status(week, age_group, &Stratum::default(), status)
+    };
+
+    assert_eq!(deaths.total_deaths, 12);
+    assert_eq!(deaths.max_week(), W01);
+    // 2022-01-02 is a Sunday of the last week of 2021.
+    assert_eq!(count(W52, OLDEST, VaccinationStatus::Unvaccinated), 2);
+    assert_eq!(count(W01, OLDEST, VaccinationStatus::Unvaccinated), 3);
+    // Two doses of Pfizer and one of Johnson&Johnson.
+    assert_eq!(count(W01, OLDEST, VaccinationStatus::TwoDoses), 2);
+    // "uzupełniająca" in ISO-8859-2 and "przypominajaca".
+    assert_eq!(count(W01, OLDEST, VaccinationStatus::ThreeDoses), 2);
+    assert_eq!(count(W01, SIXTIES, VaccinationStatus::OneDose), 1);
+    assert_eq!(count(W01, SIXTIES, VaccinationStatus::Unvaccinated), 2);
+}
+
+#[test]
+fn deaths_are_stratified() {
+    let deaths = DeathsData::new(&fixture(DEATHS)).unwrap();
+    let count =
+        |stratum: Stratum, status| deaths.by_vaccination_status(W01, OLDEST, &stratum, status);
+
+    let women = Stratum {
+        sex: Some(Sex::Female),
+        ..Stratum::default()
+    };
+    assert_eq!(count(women, VaccinationStatus::Unvaccinated), 3);
+    assert_eq!(count(women, VaccinationStatus::TwoDoses), 0);
+    assert_eq!(count(women, VaccinationStatus::ThreeDoses), 1);
+
+    let reduced_immunity = Stratum {
+        reduced_immunity: Some(true),
+        ..Stratum::default()
+    };
+    assert_eq!(count(reduced_immunity, VaccinationStatus::TwoDoses), 1);
+    assert_eq!(count(reduced_immunity, VaccinationStatus::ThreeDoses), 1);
+
+    let janssen = Stratum {
+        product: Some(Product::Janssen),
+        ..Stratum::default()
+    };
+    assert_eq!(count(janssen, VaccinationStatus::TwoDoses), 1);
+
+    let mazowieckie = Stratum {
+        region: Voivodeship::from_teryt(14),
+        ..Stratum::default()
+    };
+    assert_eq!(count(mazowieckie, VaccinationStatus::Unvaccinated), 3);
+    assert_eq!(count(mazowieckie, VaccinationStatus::TwoDoses), 1);
+
+    assert_eq!(deaths.inconsistent_counties(), 0);
+}
+
+#[test]
+fn malformed_deaths_are_accounted_for() {
+    let deaths = DeathsData::new(&fixture(DEATHS)).unwrap();
+    let rejections = &deaths.rejections;
+
+    assert_eq!(rejections.records, 10);
+    assert_eq!(rejections.reported, 17);
+    assert_eq!(rejections.dropped_records(), 2);
+    assert_eq!(rejections.dropped_reported(), 5);
+    assert_eq!(rejections.reasons["bad age"].reported, 4);
+    assert_eq!(rejections.reasons["bad age"].samples[0].line, Some(10));
+    // The count of a truncated record is unknown, so it's assumed to be a single death.
+    assert_eq!(rejections.reasons["wrong number of fields"].reported, 1);
+}
+
+#[test]
+fn infections_are_read() {
+    let cases = InfectionsData::new(&fixture(CASES)).unwrap();
+    let count = |week, age_group, status| {
+        cases.by_vaccination_status(week, age_group, &Stratum::default(), status)
+    };
+
+    assert_eq!(cases.max_week(), W01);
+    assert_eq!(cases.rejections.dropped_records(), 0);
+    assert_eq!(count(W52, OLDEST, VaccinationStatus::Unvaccinated), 9);
+    assert_eq!(count(W01, OLDEST, VaccinationStatus::Unvaccinated), 10);
+    assert_eq!(count(W01, OLDEST, VaccinationStatus::TwoDoses), 20);
+    assert_eq!(count(W01, OLDEST, VaccinationStatus::ThreeDoses), 5);
+    assert_eq!(count(W01, SIXTIES, VaccinationStatus::Unvaccinated), 7);
+}
+
+#[test]
+fn vaccinations_are_cumulative() {
+    let vaccinations = VaccinationData::new(&fixture(VACCINATIONS)).unwrap();
+
+    let people = vaccinations.sum(OLDEST, W52, &Stratum::default());
+    assert_eq!(people.at_least_one_dose, 3200);
+    // Second doses of Pfizer and first ones of Johnson&Johnson.
+    assert_eq!(people.at_least_two_doses, 3000);
+    assert_eq!(people.at_least_three_doses, 1050);
+    assert_eq!(people.one_dose, 200);
+    assert_eq!(people.two_doses, 1950);
+    assert_eq!(people.three_doses, 1050);
+
+    let people = vaccinations.sum(OLDEST, W01, &Stratum::default());
+    assert_eq!(people.at_least_one_dose, 3300);
+    assert_eq!(people.one_dose, 100);
+    assert_eq!(people.two_doses, 1650);
+    assert_eq!(people.three_doses, 1550);
+}
+
+#[test]
+fn vaccinations_carry_over_weeks_without_rows() {
+    let vaccinations = VaccinationData::new(&fixture(VACCINATIONS)).unwrap();
+
+    let people = vaccinations.sum(SIXTIES, W01, &Stratum::default());
+    assert_eq!(people.at_least_one_dose, 6000);
+    assert_eq!(people.two_doses, 3500);
+    assert_eq!(people.three_doses, 2000);
+
+    let before = vaccinations.sum(SIXTIES, YearWeek((2021, 51)), &Stratum::default());
+    assert_eq!(before.at_least_one_dose, 0);
+}
+
+#[test]
+fn vaccinations_are_stratified_by_product() {
+    let vaccinations = VaccinationData::new(&fixture(VACCINATIONS)).unwrap();
+    let janssen = Stratum {
+        product: Some(Product::Janssen),
+        ..Stratum::default()
+    };
+
+    let people = vaccinations.sum(OLDEST, W01, &janssen);
+    assert_eq!(people.at_least_one_dose, 200);
+    assert_eq!(people.one_dose, 0);
+    assert_eq!(people.two_doses, 150);
+    assert_eq!(people.three_doses, 50);
+}
+
+#[test]
+fn malformed_vaccinations_are_accounted_for() {
+    let vaccinations = VaccinationData::new(&fixture(VACCINATIONS)).unwrap();
+    let rejections = &vaccinations.rejections;
+
+    // Other countries and overlapping target groups are left out altogether, and people
+    // of regional rows are counted with the national ones already.
+    assert_eq!(rejections.records, 7);
+    assert_eq!(rejections.reported, 9350);
+    assert_eq!(rejections.reasons["unknown age"].records, 1);
+    assert_eq!(rejections.reasons["unknown age"].reported, 40);
+    assert_eq!(rejections.reasons["bad value of SecondDose"].records, 1);
+    assert_eq!(rejections.reasons["bad value of SecondDose"].reported, 10);
+}
+
+#[test]
+fn population_is_read_by_single_years_and_the_oldest_together() {
+    let ages = age_distribution(&fixture(POPULATION)).unwrap();
+    let population_of = |age_group, sex| {
+        ages.population_of(
+            age_group,
+            &Stratum {
+                sex,
+                ..Stratum::default()
+            },
+        )
+    };
+
+    // Summary rows below "85 lat i więcej" are skipped.
+    assert_eq!(ages.population(), 90_000);
+    assert_eq!(population_of(SIXTIES, None), 10_000);
+    assert_eq!(population_of(OLDEST, None), 10_000);
+    assert_eq!(population_of(OLDEST, Some(Sex::Male)), 4_400);
+    assert_eq!(population_of(OLDEST, Some(Sex::Female)), 5_600);
+    assert_eq!(population_of(AgeGroup::new(0, 4), Some(Sex::Female)), 2_600);
+}
+
+#[test]
+fn boosters_come_from_the_oldest_full_courses() {
+    let vaccinations = VaccinationData::new(&fixture(VACCINATIONS)).unwrap();
+
+    let cohorts = vaccinations.cohorts(OLDEST, W52, &Stratum::default());
+    assert_eq!(cohorts.two_doses, [(W52, 1950)]);
+    assert_eq!(cohorts.three_doses, [(W52, 1050)]);
+
+    let cohorts = vaccinations.cohorts(OLDEST, W01, &Stratum::default());
+    assert_eq!(cohorts.two_doses, [(W52, 1450), (W01, 200)]);
+    assert_eq!(cohorts.three_doses, [(W52, 1050), (W01, 500)]);
+
+    let by_week = vaccinations.cohorts_by_week(OLDEST, &Stratum::default());
+    assert_eq!(by_week.len(), 2);
+    assert_eq!(by_week[0].1.two_doses, [(W52, 1950)]);
+    assert_eq!(by_week[1].0, W01);
+    assert_eq!(by_week[1].1.two_doses, cohorts.two_doses);
+
+    let janssen = Stratum {
+        product: Some(Product::Janssen),
+        ..Stratum::default()
+    };
+    let cohorts = vaccinations.cohorts(OLDEST, W01, &janssen);
+    assert_eq!(cohorts.two_doses, [(W52, 150)]);
+    assert_eq!(cohorts.three_doses, [(W52, 50)]);
+}
+
+#[test]
+fn causes_of_errors_are_reported_once() {
+    let error = anyhow::Error::from(DeathsData::new(&fixture("missing.csv")).err().unwrap());
+    let message = format!("{:#}", error);
+    let cause = error.root_cause().to_string();
+    assert!(message.starts_with("can't open "));
+    assert_eq!(message.matches(&cause).count(), 1, "{}", message);
+}
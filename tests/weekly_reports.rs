@@ -0,0 +1,400 @@
+//! Reports of the fixture datasets checked against numbers computed by hand.
+
+mod common;
+
+use std::path::PathBuf;
+
+use common::{fixture, CASES, DEATHS, POPULATION, VACCINATIONS};
+use covid_vaccine_efficacy::{
+    datasets::Datasets,
+    deaths::VaccinationStatus,
+    demographics::{Sex, StandardPopulation},
+    export::{self, Format, MetricName, Scale},
+    regions::Voivodeship,
+    sensitivity::{protection_lags, PROTECTION_LAGS},
+    snapshots::SnapshotFiles,
+    waning::{by_time_since_dose, SinceDose},
+    AgeGroup, Stratum, YearWeek,
+};
+use parquet::{
+    file::reader::{FileReader, SerializedFileReader},
+    record::RowAccessor,
+};
+
+const W52: YearWeek = YearWeek((2021, 52));
+const W01: YearWeek = YearWeek((2022, 1));
+const OLDEST: AgeGroup = AgeGroup::open_ended(80);
+const SIXTIES: AgeGroup = AgeGroup::new(60, 69);
+
+fn datasets() -> Datasets {
+    let files = SnapshotFiles {
+        snapshot: None,
+        deaths: fixture(DEATHS),
+        cases: fixture(CASES),
+    };
+    Datasets::load(&fixture(VACCINATIONS), &fixture(POPULATION), &files).unwrap()
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!(
+        (actual - expected).abs() <= 1e-9 * expected.abs(),
+        "{} != {}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn report_of_the_oldest() {
+    let report = datasets().weekly_report(W01, OLDEST, &Stratum::default());
+
+    // 10 000 people, 3 300 of whom got at least one dose.
+    assert_eq!(report.unvaccinated_people, 6_700);
+    assert_eq!(report.vaccinated_people.two_doses, 1_650);
+    assert_eq!(report.vaccinated_people.three_doses, 1_550);
+
+    assert_eq!(report.absolute_deaths.unvaccinated, 3);
+    assert_eq!(report.absolute_deaths.two_doses, 2);
+    assert_eq!(report.absolute_deaths.three_doses, 2);
+    assert_close(
+        report.deaths_per_million.unvaccinated,
+        447.761_194_029_850_7,
+    );
+    assert_close(report.deaths_per_million.two_doses, 1_212.121_212_121_212);
+    assert_close(
+        report.deaths_per_million.three_doses,
+        1_290.322_580_645_161_3,
+    );
+
+    assert_eq!(report.absolute_cases.unvaccinated, 10);
+    assert_eq!(report.absolute_cases.two_doses, 20);
+    assert_eq!(report.absolute_cases.three_doses, 5);
+    assert_close(
+        report.cases_per_million.unvaccinated,
+        1_492.537_313_432_835_8,
+    );
+    assert_close(report.cases_per_million.two_doses, 12_121.212_121_212_12);
+    assert_close(report.cases_per_million.three_doses, 3_225.806_451_612_903);
+
+    // Halfway between 2021W52 and 2022W01.
+    assert_close(report.person_weeks.unvaccinated, 6_750.0);
+    assert_close(report.person_weeks.one_dose, 150.0);
+    assert_close(report.person_weeks.two_doses, 1_800.0);
+    assert_close(report.person_weeks.three_doses, 1_300.0);
+    assert_close(
+        report.deaths_per_100k_person_weeks.unvaccinated,
+        44.444_444_444_444_44,
+    );
+    assert_close(
+        report.deaths_per_100k_person_weeks.two_doses,
+        111.111_111_111_111_11,
+    );
+    assert_close(
+        report.cases_per_100k_person_weeks.three_doses,
+        384.615_384_615_384_6,
+    );
+
+    // (2 / 1 800) / (3 / 6 750)
+    assert_close(report.risk_ratio_of_two_doses().value, 2.5);
+    // (20 / 1 800) / (10 / 6 750)
+    assert_close(report.case_risk_ratio_of_two_doses().value, 7.5);
+    assert_close(report.cfr_unvaccinated(), 0.3);
+    assert_close(report.cfr_two_doses(), 0.1);
+    assert_close(report.cfr_three_doses(), 0.4);
+}
+
+#[test]
+fn report_of_the_last_week_of_a_year() {
+    let report = datasets().weekly_report(W52, OLDEST, &Stratum::default());
+
+    assert_eq!(report.unvaccinated_people, 6_800);
+    assert_eq!(report.vaccinated_people.two_doses, 1_950);
+    assert_eq!(report.vaccinated_people.three_doses, 1_050);
+    assert_eq!(report.absolute_deaths.unvaccinated, 2);
+    assert_eq!(report.absolute_cases.unvaccinated, 9);
+    assert_close(
+        report.deaths_per_million.unvaccinated,
+        294.117_647_058_823_5,
+    );
+    assert_close(report.deaths_per_million.two_doses, 0.0);
+}
+
+#[test]
+fn report_of_women_assumes_the_coverage_of_both_sexes() {
+    let women = Stratum {
+        sex: Some(Sex::Female),
+        ..Stratum::default()
+    };
+    let report = datasets().weekly_report(W01, OLDEST, &women);
+
+    // 5 600 of 10 000 people are women, so are 56% of the vaccinated.
+    assert_eq!(report.unvaccinated_people, 5_600 - 1_848);
+    assert_eq!(report.vaccinated_people.two_doses, 924);
+    assert_eq!(report.vaccinated_people.three_doses, 868);
+    assert_eq!(report.absolute_deaths.unvaccinated, 3);
+    assert_eq!(report.absolute_deaths.three_doses, 1);
+    assert_close(
+        report.deaths_per_million.unvaccinated,
+        799.573_560_767_590_6,
+    );
+    assert_close(report.deaths_per_million.three_doses, 1_152.073_732_718_894);
+}
+
+#[test]
+fn reports_span_the_turn_of_the_year() {
+    let datasets = datasets();
+    let weeks = datasets.weeks(Some(W52), None);
+    assert_eq!(weeks, [W52, W01]);
+
+    let reports = datasets
+        .weekly_reports(
+            &weeks,
+            &[SIXTIES, OLDEST],
+            Stratum::default(),
+            StandardPopulation::Gus,
+        )
+        .unwrap();
+    let report = reports.get(W01, SIXTIES).unwrap();
+    assert_eq!(report.unvaccinated_people, 4_000);
+    assert_eq!(report.vaccinated_people.two_doses, 3_500);
+    assert_eq!(report.absolute_deaths.unvaccinated, 2);
+    assert_eq!(report.absolute_cases.unvaccinated, 7);
+    assert_close(report.deaths_per_million.unvaccinated, 500.0);
+    // 6 000 first doses of Pfizer, 5 500 second ones.
+    assert_eq!(report.vaccinated_people.one_dose, 500);
+    assert_eq!(report.absolute_deaths.one_dose, 1);
+    assert_eq!(report.absolute_deaths.total(), 3);
+    assert_close(report.deaths_per_million.one_dose, 2_000.0);
+    // Nobody in their sixties got a dose in 2022W01, so person-weeks equal people.
+    // (1 / 500) / (2 / 4 000)
+    assert_close(report.risk_ratio_of_one_dose().value, 4.0);
+
+    // Both age groups are 10 000 people, so they weigh the same.
+    let standardized = reports.standardized(|report| report.deaths_per_million.unvaccinated);
+    assert_eq!(standardized[1].0, W01);
+    assert_close(standardized[1].1, (500.0 + 447.761_194_029_850_7) / 2.0);
+
+    // Case fatality pools deaths over cases instead of averaging 2 / 7 and 3 / 10.
+    let cfr = reports.pooled_ratio(
+        |report| report.absolute_deaths.unvaccinated as f64,
+        |report| report.absolute_cases.unvaccinated as f64,
+    );
+    assert_close(cfr[1].1, 5.0 / 17.0);
+
+    assert!(datasets
+        .validate(&weeks, &[SIXTIES, OLDEST], &[Stratum::default()])
+        .is_empty());
+}
+
+#[test]
+fn more_vaccinated_than_people_leaves_nobody_unvaccinated() {
+    let datasets = datasets();
+    let malopolskie = Stratum {
+        region: Voivodeship::from_teryt(12),
+        ..Stratum::default()
+    };
+
+    // ECDC reports 1 000 people of 80+ and 1 200 of them with a first dose by 2021W52,
+    // half of whom count as vaccinated for the week.
+    let report = datasets.weekly_report(W52, OLDEST, &malopolskie);
+    assert_eq!(report.unvaccinated_people, 0);
+    assert_close(report.person_weeks.unvaccinated, 400.0);
+    let report = datasets.weekly_report(W01, OLDEST, &malopolskie);
+    assert_eq!(report.person_weeks.unvaccinated, 0.0);
+
+    let problems = datasets.clamped_cells(&[W52], &[SIXTIES, OLDEST], &malopolskie);
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with("2021W52, grupa wiekowa 80+"));
+    assert!(datasets
+        .validate(&[W52], &[OLDEST], &[Stratum::default(), malopolskie])
+        .contains(&problems[0]));
+}
+
+#[test]
+fn recent_doses_fall_into_the_first_band() {
+    let datasets = datasets();
+    let reports = datasets
+        .weekly_reports(
+            &[W52, W01],
+            &[SIXTIES, OLDEST],
+            Stratum::default(),
+            StandardPopulation::Gus,
+        )
+        .unwrap();
+    let waning = by_time_since_dose(&datasets, &reports);
+
+    // Every dose of the fixture was given within the last two weeks.
+    assert_eq!(waning.len(), 2);
+    let two_doses = &waning[0];
+    assert_eq!(two_doses.status, VaccinationStatus::TwoDoses);
+    assert_eq!(two_doses.band, SinceDose::new(0, 12));
+    assert_eq!(two_doses.cells, 4);
+    // Half of 3 500 people in their sixties vaccinated in 2021W52, all of them in 2022W01,
+    // and halfway between 0, 1 950 and 1 650 of the oldest.
+    assert_close(two_doses.person_weeks, 8_025.0);
+    assert_close(two_doses.deaths, 2.0);
+    assert_close(two_doses.cases, 20.0);
+    assert_eq!(waning[1].status, VaccinationStatus::ThreeDoses);
+    assert_close(waning[1].deaths, 2.0);
+}
+
+#[test]
+fn protection_lag_counts_doses_of_earlier_weeks() {
+    let mut datasets = datasets();
+    datasets.protection_lag = 1;
+    let report = datasets.weekly_report(W01, OLDEST, &Stratum::default());
+
+    // Doses of 2022W01 don't count yet, so the denominators are those of 2021W52.
+    assert_eq!(report.unvaccinated_people, 6_800);
+    assert_eq!(report.vaccinated_people.two_doses, 1_950);
+    assert_eq!(report.vaccinated_people.three_doses, 1_050);
+    assert_eq!(report.absolute_deaths.two_doses, 2);
+    assert_close(report.deaths_per_million.two_doses, 1_025.641_025_641_025_6);
+
+    // Nobody got any dose before 2021W52.
+    datasets.protection_lag = 2;
+    let report = datasets.weekly_report(W01, OLDEST, &Stratum::default());
+    assert_eq!(report.unvaccinated_people, 10_000);
+    assert_eq!(report.vaccinated_people.two_doses, 0);
+}
+
+#[test]
+fn sensitivity_covers_every_protection_lag() {
+    let datasets = datasets();
+    let sensitivity = protection_lags(&datasets, &[W01], &[OLDEST], Stratum::default()).unwrap();
+
+    let lags: Vec<usize> = sensitivity.iter().map(|lag| lag.lag).collect();
+    assert_eq!(lags, PROTECTION_LAGS);
+    // A single week and age group, so pooling changes nothing.
+    let report = datasets.weekly_report(W01, OLDEST, &Stratum::default());
+    assert_close(
+        sensitivity[0].risk_ratio_of_two_doses.value,
+        report.risk_ratio_of_two_doses().value,
+    );
+    // (2 / 975) / (3 / 8 400), person-weeks of 2021W52 which nobody started vaccinated
+    assert_close(
+        sensitivity[1].risk_ratio_of_two_doses.value,
+        5.743_589_743_589_744,
+    );
+}
+
+#[test]
+fn metrics_are_looked_up_by_their_long_format_names() {
+    let report = datasets().weekly_report(W01, OLDEST, &Stratum::default());
+
+    let coverage: MetricName = "coverage:two_doses".parse().unwrap();
+    assert_eq!(coverage.to_string(), "coverage:two_doses");
+    assert_close(coverage.value(&report), 0.165);
+    let rate_ratio: MetricName = "death_rate_ratio:two_doses".parse().unwrap();
+    assert_close(rate_ratio.value(&report), 2.5);
+    assert!("death_rate_ratio:unvaccinated"
+        .parse::<MetricName>()
+        .is_err());
+    assert!(MetricName::all().any(|name| name == coverage));
+
+    // Scales come with the metrics rather than their names.
+    assert_eq!(coverage.scale(), Scale::Linear);
+    assert_eq!(rate_ratio.scale(), Scale::Logarithmic);
+    let cfr: MetricName = "cfr:two_doses".parse().unwrap();
+    assert_eq!(cfr.scale(), Scale::Linear);
+}
+
+#[test]
+fn protection_lag_leaves_out_weeks_before_the_first_protected_dose() {
+    let mut datasets = datasets();
+    datasets.protection_lag = 1;
+    let reports = datasets
+        .weekly_reports(
+            &[W52, W01],
+            &[SIXTIES, OLDEST],
+            Stratum::default(),
+            StandardPopulation::Gus,
+        )
+        .unwrap();
+    let waning = by_time_since_dose(&datasets, &reports);
+
+    // Only doses of 2021W52 count in 2022W01, and nothing counts in 2021W52.
+    let two_doses = &waning[0];
+    assert_eq!(two_doses.cells, 2);
+    // Half of 3 500 people in their sixties and of 1 950 of the oldest.
+    assert_close(two_doses.person_weeks, 2_725.0);
+}
+
+/// Observations of the fixture reports, along with a fresh directory to write them to.
+fn export_of(format: Format) -> (Vec<export::Observation>, PathBuf) {
+    let datasets = datasets();
+    let reports = datasets
+        .weekly_reports(
+            &[W52, W01],
+            &[SIXTIES, OLDEST],
+            Stratum::default(),
+            StandardPopulation::Gus,
+        )
+        .unwrap();
+    let output = std::env::temp_dir().join(format!("covid-vaccine-efficacy-{}", format));
+    std::fs::create_dir_all(&output).unwrap();
+    export::write(&reports, format, &output).unwrap();
+    (
+        export::observations(&reports),
+        output.join(format!("weekly_reports.{}", format)),
+    )
+}
+
+#[test]
+fn csv_export_writes_every_observation() {
+    let (observations, path) = export_of(Format::Csv);
+    let mut reader = csv::Reader::from_path(path).unwrap();
+    assert_eq!(
+        reader.headers().unwrap(),
+        vec!["week", "age_group", "status", "metric", "value"]
+    );
+    let records: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+    assert_eq!(records.len(), observations.len());
+    for (record, observation) in records.iter().zip(&observations) {
+        assert_eq!(&record[0], observation.week);
+        assert_eq!(&record[3], observation.metric);
+        let value: f64 = record[4].parse().unwrap();
+        assert!(value == observation.value || value.is_nan() && observation.value.is_nan());
+    }
+}
+
+#[test]
+fn json_export_writes_non_finite_values_as_null() {
+    let (observations, path) = export_of(Format::Json);
+    let json: serde_json::Value =
+        serde_json::from_reader(std::fs::File::open(path).unwrap()).unwrap();
+    let rows = json.as_array().unwrap();
+    assert_eq!(rows.len(), observations.len());
+    for (row, observation) in rows.iter().zip(&observations) {
+        assert_eq!(row["status"], observation.status);
+        match row["value"].as_f64() {
+            Some(value) => assert_close(value, observation.value),
+            None => assert!(row["value"].is_null() && !observation.value.is_finite()),
+        }
+    }
+    assert!(observations
+        .iter()
+        .any(|observation| observation.value.is_nan()));
+}
+
+#[test]
+fn parquet_export_writes_every_observation() {
+    let (observations, path) = export_of(Format::Parquet);
+    let reader = SerializedFileReader::new(std::fs::File::open(path).unwrap()).unwrap();
+    let metadata = reader.metadata();
+    assert_eq!(metadata.num_row_groups(), 1);
+    assert_eq!(
+        metadata.file_metadata().num_rows(),
+        observations.len() as i64
+    );
+    let rows: Vec<_> = reader
+        .get_row_iter(None)
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+    for (row, observation) in rows.iter().zip(&observations) {
+        assert_eq!(row.get_string(1).unwrap(), &observation.age_group);
+        let value = row.get_double(4).unwrap();
+        assert!(value == observation.value || value.is_nan() && observation.value.is_nan());
+    }
+}